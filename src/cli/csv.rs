@@ -1,13 +1,94 @@
 use crate::cli::verify_input_file;
+use crate::process::{
+    terminal_width, ConvertOptions, CsvDialect, DiffOptions, FilterOptions, FlattenOptions,
+    GenerateOptions, JoinOptions, Mapping, MaskOptions, QueryOptions, ShowOptions, SplitBy,
+    SplitOptions, StatsOptions, ToSqliteOptions, TransformOptions, ValidateOptions, WriterOptions,
+};
 use crate::CmdExecutor;
-use clap::Parser;
+use clap::{ArgAction, ArgGroup, Args, Parser};
+use encoding_rs::Encoding;
+use enum_dispatch::enum_dispatch;
 use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, IsTerminal, Write};
 use std::str::FromStr;
 
+/// `rcli csv -i input.csv --format json` without a subcommand still converts, like `csv convert`
 #[derive(Debug, Parser, Serialize, Deserialize)]
+#[command(args_conflicts_with_subcommands = true, arg_required_else_help = true)]
 pub struct CsvOpts {
-    #[arg(short, long, value_parser = verify_input_file)]
+    #[command(subcommand)]
+    pub cmd: Option<CsvSubCommand>,
+
+    #[command(flatten)]
+    pub convert: Option<CsvConvertOpts>,
+}
+
+impl CmdExecutor for CsvOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        match (self.cmd, self.convert) {
+            (Some(cmd), _) => cmd.execute().await,
+            (None, Some(convert)) => convert.execute().await,
+            (None, None) => anyhow::bail!("--input is required to convert without a subcommand"),
+        }
+    }
+}
+
+#[enum_dispatch(CmdExecutor)]
+#[derive(Debug, Parser, Serialize, Deserialize)]
+pub enum CsvSubCommand {
+    #[command(about = "Convert CSV to other formats")]
+    Convert(Box<CsvConvertOpts>),
+
+    #[command(
+        name = "from-json",
+        about = "Convert JSON, NDJSON or YAML records to CSV"
+    )]
+    FromJson(CsvFromJsonOpts),
+
+    #[command(about = "Show CSV as a table in the terminal")]
+    Show(CsvShowOpts),
+
+    #[command(about = "Run a SQL query over CSV files, each file is a table named after it")]
+    Query(CsvQueryOpts),
+
+    #[command(about = "Profile the columns of a CSV file")]
+    Stats(CsvStatsOpts),
+
+    #[command(about = "Validate a CSV file against a YAML, JSON or TOML schema")]
+    Validate(CsvValidateOpts),
+
+    #[command(about = "Join two CSV files on key columns")]
+    Join(CsvJoinOpts),
+
+    #[command(about = "Show added, removed and changed rows between two CSV files")]
+    Diff(CsvDiffOpts),
+
+    #[command(about = "Split a CSV file into chunks by rows, size or column value")]
+    Split(CsvSplitOpts),
+
+    #[command(about = "Mask or pseudonymize columns before sharing a CSV file")]
+    Mask(CsvMaskOpts),
+
+    #[command(about = "Generate synthetic CSV data from a schema")]
+    Generate(CsvGenerateOpts),
+
+    #[command(name = "to-sqlite", about = "Load a CSV file into a SQLite table")]
+    ToSqlite(CsvToSqliteOpts),
+
+    #[command(
+        name = "from-sqlite",
+        about = "Export the result of a SQLite query or table"
+    )]
+    FromSqlite(CsvFromSqliteOpts),
+}
+
+#[derive(Debug, Parser, Serialize, Deserialize)]
+#[group(id = "convert")]
+pub struct CsvConvertOpts {
+    // clap 不会为含有 flatten 字段的结构体填充参数组，`rcli csv` 靠 input 判断是否转换
+    #[arg(short, long, value_parser = verify_input_file, group = "convert")]
     pub input: String,
 
     #[arg(short, long)] // "output.json".into()
@@ -16,21 +97,717 @@ pub struct CsvOpts {
     #[arg(long, value_parser = parse_format, default_value = "JSON")]
     pub format: OutputFormat,
 
-    #[arg(short, long, default_value_t = ',')]
+    #[command(flatten)]
+    pub dialect: CsvDialectOpts,
+
+    /// Infer integer, float, boolean, null and date columns
+    #[arg(long, action = ArgAction::Set, default_value_t = true)]
+    pub infer: bool,
+
+    /// Override column types, e.g. "Kit Number=int,DOB=date:%b %d, %Y"
+    #[arg(long, value_parser = parse_types)]
+    pub types: Option<ColumnTypes>,
+
+    /// Rename columns, e.g. "Kit Number=kit"
+    #[arg(long, value_delimiter = ',')]
+    pub rename: Vec<String>,
+
+    /// Reformat a date column, e.g. "DOB=%b %d, %Y->%Y-%m-%d", ISO 8601 when no output format is given
+    #[arg(long)]
+    pub date_format: Vec<String>,
+
+    /// Comma separated columns to trim, "*" for all columns
+    #[arg(long, value_delimiter = ',')]
+    pub trim: Vec<String>,
+
+    /// Comma separated columns to uppercase
+    #[arg(long, value_delimiter = ',')]
+    pub upper: Vec<String>,
+
+    /// Comma separated columns to lowercase
+    #[arg(long, value_delimiter = ',')]
+    pub lower: Vec<String>,
+
+    /// Regex replacement, e.g. "Position=^(\w)\w+$->$1"
+    #[arg(long)]
+    pub replace: Vec<String>,
+
+    /// Add a computed column, e.g. "age = years_since(DOB)"
+    #[arg(long)]
+    pub compute: Vec<String>,
+
+    /// Comma separated columns to output
+    #[arg(long, value_delimiter = ',')]
+    pub select: Vec<String>,
+
+    /// Keep records matching the expression, e.g. "Position == 'Defender' && Kit Number > 10"
+    #[arg(long = "where")]
+    pub filter: Option<String>,
+
+    /// Comma separated sort keys, append ":desc" for descending order
+    #[arg(long, value_delimiter = ',')]
+    pub sort_by: Vec<String>,
+
+    /// Keep only the first record for each distinct value of these columns
+    #[arg(long, value_delimiter = ',')]
+    pub unique: Vec<String>,
+
+    /// Mapping file (YAML, JSON or TOML) to rename, cast, default, drop and nest columns
+    #[arg(long, value_parser = verify_input_file)]
+    pub mapping: Option<String>,
+
+    /// Build nested objects from headers like "player.name" and arrays from "tags[0]",
+    /// optionally with another separator than "."
+    #[arg(long, num_args = 0..=1, default_missing_value = ".")]
+    pub unflatten: Option<String>,
+
+    /// Stylesheet embedded into HTML output
+    #[arg(long, value_parser = verify_input_file)]
+    pub css: Option<String>,
+
+    /// Compression of Parquet (snappy, zstd, none) or Arrow IPC (zstd, none) output
+    #[arg(long, value_parser = parse_compression)]
+    pub compression: Option<Compression>,
+
+    /// Parse large files in parallel, on one thread per CPU unless a number is given.
+    /// The output is still serialized on a single thread.
+    #[arg(long, num_args = 0..=1, default_missing_value = "0")]
+    pub threads: Option<usize>,
+}
+
+#[derive(Debug, Args, Serialize, Deserialize)]
+pub struct CsvDialectOpts {
+    #[arg(short, long, value_parser = verify_ascii, default_value_t = ',')]
     pub delimiter: char,
 
-    #[arg(long, default_value_t = true)]
+    #[arg(long, action = ArgAction::Set, default_value_t = true)]
     pub header: bool,
+
+    #[arg(long, value_parser = verify_ascii, default_value_t = '"')]
+    pub quote: char,
+
+    /// Escape character for quotes inside quoted fields, quotes are doubled by default
+    #[arg(long, value_parser = verify_ascii)]
+    pub escape: Option<char>,
+
+    /// Skip lines starting with this character
+    #[arg(long, value_parser = verify_ascii)]
+    pub comment: Option<char>,
+
+    /// Allow records with a different number of fields
+    #[arg(long, default_value_t = false)]
+    pub flexible: bool,
+
+    /// Detect delimiter, quote and header from the first N lines
+    #[arg(long, num_args = 0..=1, default_missing_value = "20")]
+    pub sniff: Option<usize>,
+
+    /// Input encoding such as utf-16le, windows-1252 or gbk, detected by default
+    #[arg(long, value_parser = verify_encoding, default_value = "auto")]
+    pub encoding: String,
+
+    /// Sheet name or zero-based index for .xlsx, .xls or .ods input, the first sheet by default
+    #[arg(long)]
+    pub sheet: Option<String>,
+
+    /// Cell range to read from the sheet, e.g. A1:D10
+    #[arg(long)]
+    pub range: Option<String>,
 }
 
-impl CmdExecutor for CsvOpts {
+impl From<&CsvDialectOpts> for CsvDialect {
+    fn from(opts: &CsvDialectOpts) -> Self {
+        Self {
+            delimiter: opts.delimiter as u8,
+            has_header: opts.header,
+            quote: opts.quote as u8,
+            escape: opts.escape.map(|c| c as u8),
+            comment: opts.comment.map(|c| c as u8),
+            flexible: opts.flexible,
+            sniff: opts.sniff,
+            encoding: Encoding::for_label(opts.encoding.as_bytes()),
+            sheet: opts.sheet.clone(),
+            range: opts.range.clone(),
+        }
+    }
+}
+
+impl CmdExecutor for CsvConvertOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        if self.compression.is_some() && !self.format.is_columnar() {
+            anyhow::bail!(
+                "--compression only applies to parquet and arrow output, not {}",
+                self.format.extension()
+            );
+        }
+        let output = output_path(self.output.as_ref(), self.format.extension());
+        let opts = ConvertOptions {
+            format: self.format,
+            dialect: (&self.dialect).into(),
+            infer: self.infer,
+            types: self.types.unwrap_or_default(),
+            transform: TransformOptions {
+                rename: self.rename,
+                date_format: self.date_format,
+                trim: self.trim,
+                upper: self.upper,
+                lower: self.lower,
+                replace: self.replace,
+                compute: self.compute,
+            },
+            filter: FilterOptions {
+                select: self.select,
+                filter: self.filter,
+                sort_by: self.sort_by,
+                unique: self.unique,
+            },
+            mapping: self.mapping.as_deref().map(Mapping::load).transpose()?,
+            unflatten: self.unflatten,
+            writer: WriterOptions {
+                css: self.css.map(fs::read_to_string).transpose()?,
+                compression: self.compression,
+                ..Default::default()
+            },
+            threads: self.threads,
+        };
+        crate::process::process_csv(&self.input, output, &opts)
+    }
+}
+
+#[derive(Debug, Parser, Serialize, Deserialize)]
+pub struct CsvFromJsonOpts {
+    #[arg(short, long, value_parser = verify_input_file, default_value = "-")]
+    pub input: String,
+
+    #[arg(short, long)]
+    pub output: Option<String>,
+
+    /// Input format, detected from the content when omitted
+    #[arg(long, value_parser = parse_input_format)]
+    pub format: Option<InputFormat>,
+
+    #[arg(short, long, value_parser = verify_ascii, default_value_t = ',')]
+    pub delimiter: char,
+
+    /// Separator between the keys of flattened nested objects
+    #[arg(long, default_value = ".")]
+    pub separator: String,
+
+    /// How to write arrays: join the elements, explode them into rows, or index them as columns
+    #[arg(long, value_parser = parse_array_mode, default_value = "join")]
+    pub arrays: ArrayMode,
+
+    /// Separator used when joining array elements
+    #[arg(long, default_value = ";")]
+    pub array_separator: String,
+}
+
+impl CmdExecutor for CsvFromJsonOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let output = output_path(self.output.as_ref(), "csv");
+        let opts = FlattenOptions {
+            separator: self.separator,
+            arrays: self.arrays,
+            array_separator: self.array_separator,
+        };
+        crate::process::process_json_to_csv(
+            &self.input,
+            output,
+            self.format,
+            self.delimiter as u8,
+            &opts,
+        )
+    }
+}
+
+#[derive(Debug, Parser, Serialize, Deserialize)]
+pub struct CsvShowOpts {
+    #[arg(short, long, value_parser = verify_input_file, default_value = "-")]
+    pub input: String,
+
+    #[command(flatten)]
+    pub dialect: CsvDialectOpts,
+
+    /// Show only the first N records
+    #[arg(long)]
+    pub head: Option<usize>,
+
+    /// Show only the last N records
+    #[arg(long)]
+    pub tail: Option<usize>,
+
+    /// Comma separated columns to show
+    #[arg(long, value_delimiter = ',')]
+    pub columns: Vec<String>,
+
+    /// Table width, defaults to the terminal width
+    #[arg(long)]
+    pub width: Option<usize>,
+
+    /// Page through the table when the output is a terminal
+    #[arg(long, action = ArgAction::Set, default_value_t = true)]
+    pub pager: bool,
+}
+
+impl CmdExecutor for CsvShowOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let opts = ShowOptions {
+            dialect: (&self.dialect).into(),
+            head: self.head,
+            tail: self.tail,
+            columns: self.columns,
+            width: self.width,
+            pager: self.pager,
+        };
+        crate::process::process_show(&self.input, &opts)
+    }
+}
+
+#[derive(Debug, Parser, Serialize, Deserialize)]
+pub struct CsvQueryOpts {
+    /// SQL query, e.g. "SELECT Nationality, COUNT(*) FROM juventus GROUP BY Nationality"
+    pub sql: String,
+
+    #[arg(short, long = "input", value_parser = verify_input_file, required = true)]
+    pub inputs: Vec<String>,
+
+    /// Write to output/<OUTPUT> instead of stdout
+    #[arg(short, long)]
+    pub output: Option<String>,
+
+    #[arg(long, value_parser = parse_format, default_value = "JSON")]
+    pub format: OutputFormat,
+
+    #[command(flatten)]
+    pub dialect: CsvDialectOpts,
+
+    /// Override column types, e.g. "Kit Number=int,DOB=date:%b %d, %Y"
+    #[arg(long, value_parser = parse_types)]
+    pub types: Option<ColumnTypes>,
+}
+
+impl CmdExecutor for CsvQueryOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let opts = QueryOptions {
+            format: self.format,
+            dialect: (&self.dialect).into(),
+            types: self.types.unwrap_or_default(),
+        };
+        let output = output_writer(self.output.as_ref())?;
+        crate::process::process_query(&self.sql, &self.inputs, output, &opts)
+    }
+}
+
+#[derive(Debug, Parser, Serialize, Deserialize)]
+pub struct CsvStatsOpts {
+    #[arg(short, long, value_parser = verify_input_file, default_value = "-")]
+    pub input: String,
+
+    #[command(flatten)]
+    pub dialect: CsvDialectOpts,
+
+    /// Override column types, e.g. "Kit Number=int,DOB=date:%b %d, %Y"
+    #[arg(long, value_parser = parse_types)]
+    pub types: Option<ColumnTypes>,
+
+    /// Number of most frequent values to report
+    #[arg(long, default_value_t = 5)]
+    pub top: usize,
+
+    /// Distinct values counted exactly before switching to a HyperLogLog estimate
+    #[arg(long, default_value_t = 100_000)]
+    pub distinct_limit: usize,
+
+    #[arg(long, value_parser = parse_report_format, default_value = "table")]
+    pub format: ReportFormat,
+
+    /// Table width, defaults to the terminal width
+    #[arg(long)]
+    pub width: Option<usize>,
+}
+
+impl CmdExecutor for CsvStatsOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let opts = StatsOptions {
+            dialect: (&self.dialect).into(),
+            types: self.types.unwrap_or_default(),
+            top: self.top,
+            distinct_limit: self.distinct_limit,
+            format: self.format,
+            width: self.width.unwrap_or_else(terminal_width),
+        };
+        crate::process::process_stats(&self.input, &opts)
+    }
+}
+
+#[derive(Debug, Parser, Serialize, Deserialize)]
+pub struct CsvValidateOpts {
+    #[arg(short, long, value_parser = verify_input_file, default_value = "-")]
+    pub input: String,
+
+    /// Schema file declaring the columns, in YAML, JSON or TOML
+    #[arg(short, long, value_parser = verify_input_file)]
+    pub schema: String,
+
+    #[command(flatten)]
+    pub dialect: CsvDialectOpts,
+
+    #[arg(long, value_parser = parse_report_format, default_value = "table")]
+    pub format: ReportFormat,
+}
+
+impl CmdExecutor for CsvValidateOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let opts = ValidateOptions {
+            dialect: (&self.dialect).into(),
+            format: self.format,
+        };
+        crate::process::process_validate(&self.input, &self.schema, &opts)
+    }
+}
+
+#[derive(Debug, Parser, Serialize, Deserialize)]
+pub struct CsvJoinOpts {
+    #[arg(short, long, value_parser = verify_input_file)]
+    pub left: String,
+
+    #[arg(short, long, value_parser = verify_input_file)]
+    pub right: String,
+
+    /// Comma separated key columns, use "left=right" when the names differ
+    #[arg(long, value_delimiter = ',', required = true)]
+    pub on: Vec<String>,
+
+    #[arg(long, value_parser = parse_join_mode, default_value = "inner")]
+    pub mode: JoinMode,
+
+    /// Write to output/<OUTPUT> instead of stdout
+    #[arg(short, long)]
+    pub output: Option<String>,
+
+    #[arg(long, value_parser = parse_format, default_value = "csv")]
+    pub format: OutputFormat,
+
+    #[command(flatten)]
+    pub dialect: CsvDialectOpts,
+
+    /// Override column types, e.g. "Kit Number=int,DOB=date:%b %d, %Y"
+    #[arg(long, value_parser = parse_types)]
+    pub types: Option<ColumnTypes>,
+
+    /// Prefix of left columns whose name also exists on the right
+    #[arg(long, default_value = "left_")]
+    pub left_prefix: String,
+
+    /// Prefix of right columns whose name also exists on the left
+    #[arg(long, default_value = "right_")]
+    pub right_prefix: String,
+}
+
+impl CmdExecutor for CsvJoinOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let opts = JoinOptions {
+            mode: self.mode,
+            on: self.on,
+            format: self.format,
+            dialect: (&self.dialect).into(),
+            types: self.types.unwrap_or_default(),
+            left_prefix: self.left_prefix,
+            right_prefix: self.right_prefix,
+        };
+        let output = output_writer(self.output.as_ref())?;
+        crate::process::process_join(&self.left, &self.right, output, &opts)
+    }
+}
+
+#[derive(Debug, Parser, Serialize, Deserialize)]
+pub struct CsvDiffOpts {
+    #[arg(value_parser = verify_input_file)]
+    pub old: String,
+
+    #[arg(value_parser = verify_input_file)]
+    pub new: String,
+
+    /// Comma separated key columns, rows are compared by position without a key
+    #[arg(short, long, value_delimiter = ',')]
+    pub key: Vec<String>,
+
+    #[arg(long, value_parser = parse_diff_format, default_value = "text")]
+    pub format: DiffFormat,
+
+    /// Write to output/<OUTPUT> instead of stdout
+    #[arg(short, long)]
+    pub output: Option<String>,
+
+    #[command(flatten)]
+    pub dialect: CsvDialectOpts,
+}
+
+impl CmdExecutor for CsvDiffOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let opts = DiffOptions {
+            dialect: (&self.dialect).into(),
+            keys: self.key,
+            format: self.format,
+            // 写入文件时不输出颜色控制符
+            color: self.output.is_none() && io::stdout().is_terminal(),
+        };
+        let output = output_writer(self.output.as_ref())?;
+        crate::process::process_diff(&self.old, &self.new, output, &opts)
+    }
+}
+
+#[derive(Debug, Parser, Serialize, Deserialize)]
+#[command(group(ArgGroup::new("chunk").required(true).args(["rows", "bytes", "by"])))]
+pub struct CsvSplitOpts {
+    #[arg(short, long, value_parser = verify_input_file)]
+    pub input: String,
+
+    /// Maximum number of records per chunk
+    #[arg(long)]
+    pub rows: Option<usize>,
+
+    /// Approximate maximum size per chunk, e.g. 512K or 10MB
+    #[arg(long, value_parser = parse_size)]
+    pub bytes: Option<u64>,
+
+    /// Write one chunk per distinct value of this column
+    #[arg(long)]
+    pub by: Option<String>,
+
+    /// File name template, {stem} is the input name, {index} the chunk number and {value} the column value
+    #[arg(short, long)]
+    pub template: Option<String>,
+
+    #[arg(long, default_value = "output")]
+    pub output_dir: String,
+
+    #[command(flatten)]
+    pub dialect: CsvDialectOpts,
+}
+
+impl CmdExecutor for CsvSplitOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let by = match (self.rows, self.bytes, self.by) {
+            (Some(rows), _, _) => SplitBy::Rows(rows),
+            (_, Some(bytes), _) => SplitBy::Bytes(bytes),
+            (_, _, Some(column)) => SplitBy::Column(column),
+            _ => anyhow::bail!("One of --rows, --bytes or --by is required"),
+        };
+        let opts = SplitOptions {
+            by,
+            dialect: (&self.dialect).into(),
+            template: self.template,
+            output_dir: self.output_dir,
+        };
+        for (path, rows) in crate::process::process_split(&self.input, &opts)? {
+            println!("{}\t{} rows", path.display(), rows);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Parser, Serialize, Deserialize)]
+pub struct CsvMaskOpts {
+    #[arg(short, long, value_parser = verify_input_file)]
+    pub input: String,
+
+    /// Column and strategy, e.g. "Name=hash", "DOB=year-only" or "email=redact".
+    /// Strategies: hash[:LEN], fake, redact[:TEXT], year-only, partial[:KEEP], null
+    #[arg(short, long, required = true)]
+    pub column: Vec<String>,
+
+    /// Blake3 key file for hash and fake, the same key gives the same pseudonyms across files
+    #[arg(short, long, value_parser = verify_input_file)]
+    pub key: Option<String>,
+
+    /// Write to output/<OUTPUT> instead of stdout
+    #[arg(short, long)]
+    pub output: Option<String>,
+
+    #[command(flatten)]
+    pub dialect: CsvDialectOpts,
+}
+
+impl CmdExecutor for CsvMaskOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let opts = MaskOptions {
+            columns: self.column,
+            key: self.key,
+            dialect: (&self.dialect).into(),
+        };
+        let output = output_writer(self.output.as_ref())?;
+        crate::process::process_mask(&self.input, output, &opts)
+    }
+}
+
+#[derive(Debug, Parser, Serialize, Deserialize)]
+pub struct CsvGenerateOpts {
+    /// Schema with column types, ranges, enums and generators
+    #[arg(short, long, value_parser = verify_input_file)]
+    pub schema: String,
+
+    #[arg(short = 'n', long, default_value_t = 100)]
+    pub rows: usize,
+
+    /// Seed for reproducible data, a random seed is logged when omitted
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    #[arg(long, value_parser = parse_format, default_value = "csv")]
+    pub format: OutputFormat,
+
+    /// Write to output/<OUTPUT> instead of stdout
+    #[arg(short, long)]
+    pub output: Option<String>,
+}
+
+impl CmdExecutor for CsvGenerateOpts {
     async fn execute(self) -> anyhow::Result<()> {
-        let output = if let Some(output) = self.output.as_ref() {
-            format!("output/{}", output.clone())
-        } else {
-            format!("output/output.{}", self.format)
+        let opts = GenerateOptions {
+            rows: self.rows,
+            seed: self.seed,
+            format: self.format,
+            writer: WriterOptions::default(),
         };
-        crate::process::process_csv(&self.input, output, self.format)
+        let output = output_writer(self.output.as_ref())?;
+        crate::process::process_csv_generate(&self.schema, output, &opts)
+    }
+}
+
+#[derive(Debug, Parser, Serialize, Deserialize)]
+pub struct CsvToSqliteOpts {
+    #[arg(short, long, value_parser = verify_input_file)]
+    pub input: String,
+
+    /// SQLite database file, created when missing
+    #[arg(long)]
+    pub db: String,
+
+    /// Table name, defaults to the input file name
+    #[arg(short, long)]
+    pub table: Option<String>,
+
+    /// Comma separated columns to index, repeat for several indexes
+    #[arg(long)]
+    pub index: Vec<String>,
+
+    /// What to do when the table exists: fail, replace or append
+    #[arg(long, value_parser = parse_if_exists, default_value = "fail")]
+    pub if_exists: IfExists,
+
+    #[command(flatten)]
+    pub dialect: CsvDialectOpts,
+
+    /// Override column types, e.g. "Kit Number=int,DOB=date:%b %d, %Y"
+    #[arg(long, value_parser = parse_types)]
+    pub types: Option<ColumnTypes>,
+}
+
+impl CmdExecutor for CsvToSqliteOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let opts = ToSqliteOptions {
+            table: self.table,
+            dialect: (&self.dialect).into(),
+            types: self.types.unwrap_or_default(),
+            indexes: self.index,
+            if_exists: self.if_exists,
+        };
+        let (table, rows) = crate::process::process_to_sqlite(&self.input, &self.db, &opts)?;
+        println!("{}\t{}\t{} rows", self.db, table, rows);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Parser, Serialize, Deserialize)]
+#[command(group(ArgGroup::new("source").required(true).args(["query", "table"])))]
+pub struct CsvFromSqliteOpts {
+    #[arg(long, value_parser = verify_input_file)]
+    pub db: String,
+
+    /// SQL query, e.g. "SELECT * FROM players WHERE Nationality = 'Italy'"
+    #[arg(short, long)]
+    pub query: Option<String>,
+
+    /// Export a whole table
+    #[arg(short, long)]
+    pub table: Option<String>,
+
+    #[arg(long, value_parser = parse_format, default_value = "csv")]
+    pub format: OutputFormat,
+
+    /// Write to output/<OUTPUT> instead of stdout
+    #[arg(short, long)]
+    pub output: Option<String>,
+}
+
+impl CmdExecutor for CsvFromSqliteOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let sql = match (self.query, self.table) {
+            (Some(query), _) => query,
+            (None, Some(table)) => format!("SELECT * FROM \"{}\"", table.replace('"', "\"\"")),
+            (None, None) => unreachable!("clap requires a query or a table"),
+        };
+        let output = output_writer(self.output.as_ref())?;
+        crate::process::process_from_sqlite(&self.db, &sql, output, self.format)
+    }
+}
+
+fn output_writer(output: Option<&String>) -> anyhow::Result<Box<dyn Write>> {
+    match output {
+        Some(output) => {
+            let file = File::create(format!("output/{}", output))?;
+            Ok(Box::new(BufWriter::new(file)))
+        }
+        None => Ok(Box::new(BufWriter::new(io::stdout().lock()))),
+    }
+}
+
+fn output_path(output: Option<&String>, extension: &str) -> String {
+    if let Some(output) = output {
+        format!("output/{}", output)
+    } else {
+        format!("output/output.{}", extension)
+    }
+}
+
+/// Parse a size like `512`, `64K`, `10MB` or `1.5GiB`, units are powers of 1024
+fn parse_size(value: &str) -> Result<u64, anyhow::Error> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid size {:?}", value))?;
+    let scale: u64 = match unit.trim().to_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        _ => anyhow::bail!("Invalid size unit {:?}", unit),
+    };
+    Ok((number * scale as f64) as u64)
+}
+
+fn verify_encoding(value: &str) -> Result<String, String> {
+    if value.eq_ignore_ascii_case("auto") || Encoding::for_label(value.as_bytes()).is_some() {
+        Ok(value.to_string())
+    } else {
+        Err(format!("Unknown encoding {:?}", value))
+    }
+}
+
+fn verify_ascii(value: &str) -> Result<char, &'static str> {
+    let value = match value {
+        "\\t" | "tab" => "\t",
+        _ => value,
+    };
+    let mut chars = value.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii() => Ok(c),
+        _ => Err("Must be a single ASCII character"),
     }
 }
 
@@ -42,7 +819,15 @@ impl From<OutputFormat> for &'static str {
     fn from(format: OutputFormat) -> Self {
         match format {
             OutputFormat::Json => "json",
+            OutputFormat::Ndjson => "ndjson",
             OutputFormat::Yaml => "yaml",
+            OutputFormat::Toml => "toml",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Xlsx => "xlsx",
+            OutputFormat::Markdown => "markdown",
+            OutputFormat::Html => "html",
+            OutputFormat::Parquet => "parquet",
+            OutputFormat::Arrow => "arrow",
         }
     }
 }
@@ -53,7 +838,15 @@ impl FromStr for OutputFormat {
     fn from_str(value: &str) -> Result<Self, Self::Err> {
         match value.to_lowercase().as_str() {
             "json" => Ok(OutputFormat::Json),
+            "ndjson" | "jsonl" => Ok(OutputFormat::Ndjson),
             "yaml" => Ok(OutputFormat::Yaml),
+            "toml" => Ok(OutputFormat::Toml),
+            "csv" => Ok(OutputFormat::Csv),
+            "xlsx" | "excel" => Ok(OutputFormat::Xlsx),
+            "markdown" | "md" => Ok(OutputFormat::Markdown),
+            "html" | "htm" => Ok(OutputFormat::Html),
+            "parquet" => Ok(OutputFormat::Parquet),
+            "arrow" | "ipc" | "feather" => Ok(OutputFormat::Arrow),
             _ => Err(anyhow::anyhow!("Unsupported format {:?}", value)),
         }
     }
@@ -68,5 +861,335 @@ impl fmt::Display for OutputFormat {
 #[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub enum OutputFormat {
     Json,
+    /// One JSON document per line (JSON Lines)
+    Ndjson,
+    Yaml,
+    /// An array of tables named `records`
+    Toml,
+    Csv,
+    /// Excel workbook with a single sheet
+    Xlsx,
+    /// GitHub-flavored pipe table
+    Markdown,
+    /// Standalone HTML document with a table
+    Html,
+    /// Apache Parquet file, snappy compressed by default
+    Parquet,
+    /// Arrow IPC file
+    Arrow,
+}
+
+impl OutputFormat {
+    /// Default file extension of the output
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Markdown => "md",
+            _ => (*self).into(),
+        }
+    }
+
+    /// Parquet and Arrow IPC, the formats with a schema and compression
+    pub fn is_columnar(&self) -> bool {
+        matches!(self, OutputFormat::Parquet | OutputFormat::Arrow)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Compression {
+    None,
+    Snappy,
+    Zstd,
+}
+
+fn parse_compression(compression: &str) -> Result<Compression, anyhow::Error> {
+    compression.parse()
+}
+
+impl FromStr for Compression {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "none" | "uncompressed" => Ok(Compression::None),
+            "snappy" => Ok(Compression::Snappy),
+            "zstd" => Ok(Compression::Zstd),
+            _ => Err(anyhow::anyhow!("Unsupported compression {:?}", value)),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InputFormat {
+    Json,
+    Ndjson,
     Yaml,
 }
+
+fn parse_input_format(format: &str) -> Result<InputFormat, anyhow::Error> {
+    format.parse()
+}
+
+impl FromStr for InputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "json" => Ok(InputFormat::Json),
+            "ndjson" | "jsonl" => Ok(InputFormat::Ndjson),
+            "yaml" | "yml" => Ok(InputFormat::Yaml),
+            _ => Err(anyhow::anyhow!("Unsupported input format {:?}", value)),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JoinMode {
+    Inner,
+    Left,
+    Right,
+    Full,
+}
+
+fn parse_join_mode(mode: &str) -> Result<JoinMode, anyhow::Error> {
+    mode.parse()
+}
+
+impl FromStr for JoinMode {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "inner" => Ok(JoinMode::Inner),
+            "left" => Ok(JoinMode::Left),
+            "right" => Ok(JoinMode::Right),
+            "full" | "outer" => Ok(JoinMode::Full),
+            _ => Err(anyhow::anyhow!("Unsupported join mode {:?}", value)),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IfExists {
+    Fail,
+    /// Drop the table and create it again
+    Replace,
+    Append,
+}
+
+fn parse_if_exists(value: &str) -> Result<IfExists, anyhow::Error> {
+    value.parse()
+}
+
+impl FromStr for IfExists {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "fail" => Ok(IfExists::Fail),
+            "replace" => Ok(IfExists::Replace),
+            "append" => Ok(IfExists::Append),
+            _ => Err(anyhow::anyhow!("Unsupported --if-exists {:?}", value)),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DiffFormat {
+    /// Colored terminal view
+    Text,
+    Json,
+    /// Patch CSV with a `_change` column
+    Csv,
+}
+
+fn parse_diff_format(format: &str) -> Result<DiffFormat, anyhow::Error> {
+    format.parse()
+}
+
+impl FromStr for DiffFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "text" | "human" => Ok(DiffFormat::Text),
+            "json" => Ok(DiffFormat::Json),
+            "csv" | "patch" => Ok(DiffFormat::Csv),
+            _ => Err(anyhow::anyhow!("Unsupported diff format {:?}", value)),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReportFormat {
+    Table,
+    Json,
+}
+
+fn parse_report_format(format: &str) -> Result<ReportFormat, anyhow::Error> {
+    format.parse()
+}
+
+impl FromStr for ReportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "table" | "text" | "human" => Ok(ReportFormat::Table),
+            "json" => Ok(ReportFormat::Json),
+            _ => Err(anyhow::anyhow!("Unsupported report format {:?}", value)),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ArrayMode {
+    Join,
+    Explode,
+    Index,
+}
+
+fn parse_array_mode(mode: &str) -> Result<ArrayMode, anyhow::Error> {
+    mode.parse()
+}
+
+impl FromStr for ArrayMode {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "join" => Ok(ArrayMode::Join),
+            "explode" => Ok(ArrayMode::Explode),
+            "index" => Ok(ArrayMode::Index),
+            _ => Err(anyhow::anyhow!("Unsupported array mode {:?}", value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum ColumnType {
+    String,
+    Integer,
+    Float,
+    Boolean,
+    Null,
+    /// A date or datetime, RFC3339 when no chrono format is given
+    Date(Option<String>),
+}
+
+impl FromStr for ColumnType {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if let Some(format) = value.trim().strip_prefix("date:") {
+            return Ok(ColumnType::Date(Some(format.to_string())));
+        }
+        match value.trim().to_lowercase().as_str() {
+            "string" | "str" | "text" => Ok(ColumnType::String),
+            "int" | "integer" => Ok(ColumnType::Integer),
+            "float" | "number" => Ok(ColumnType::Float),
+            "bool" | "boolean" => Ok(ColumnType::Boolean),
+            "null" | "empty" => Ok(ColumnType::Null),
+            "date" => Ok(ColumnType::Date(None)),
+            _ => Err(anyhow::anyhow!("Unsupported column type {:?}", value)),
+        }
+    }
+}
+
+impl fmt::Display for ColumnType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColumnType::String => write!(f, "string"),
+            ColumnType::Integer => write!(f, "int"),
+            ColumnType::Float => write!(f, "float"),
+            ColumnType::Boolean => write!(f, "bool"),
+            ColumnType::Null => write!(f, "null"),
+            ColumnType::Date(None) => write!(f, "date"),
+            ColumnType::Date(Some(format)) => write!(f, "date:{}", format),
+        }
+    }
+}
+
+impl TryFrom<String> for ColumnType {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<ColumnType> for String {
+    fn from(ty: ColumnType) -> Self {
+        ty.to_string()
+    }
+}
+
+/// Column type overrides given as `name=type` pairs
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ColumnTypes(pub Vec<(String, ColumnType)>);
+
+impl ColumnTypes {
+    pub fn get(&self, name: &str) -> Option<&ColumnType> {
+        self.0.iter().find(|(n, _)| n == name).map(|(_, ty)| ty)
+    }
+}
+
+impl FromStr for ColumnTypes {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        // 日期格式中可能包含逗号，没有 '=' 的片段属于上一个类型定义
+        let mut pairs: Vec<String> = Vec::new();
+        for part in value.split(',') {
+            match pairs.last_mut() {
+                Some(last) if !part.contains('=') => {
+                    last.push(',');
+                    last.push_str(part);
+                }
+                _ => pairs.push(part.to_string()),
+            }
+        }
+
+        let types = pairs
+            .iter()
+            .map(|pair| {
+                let (name, ty) = pair
+                    .split_once('=')
+                    .ok_or_else(|| anyhow::anyhow!("Invalid type override {:?}", pair))?;
+                Ok((name.trim().to_string(), ty.parse()?))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(ColumnTypes(types))
+    }
+}
+
+fn parse_types(value: &str) -> Result<ColumnTypes, anyhow::Error> {
+    value.parse()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_types() -> anyhow::Result<()> {
+        let types: ColumnTypes = "Kit Number=int,DOB=date:%b %d, %Y".parse()?;
+        assert_eq!(types.get("Kit Number"), Some(&ColumnType::Integer));
+        assert_eq!(
+            types.get("DOB"),
+            Some(&ColumnType::Date(Some("%b %d, %Y".into())))
+        );
+        assert!("Kit Number".parse::<ColumnTypes>().is_err());
+        assert!("Kit Number=decimal".parse::<ColumnTypes>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_size() -> anyhow::Result<()> {
+        assert_eq!(parse_size("512")?, 512);
+        assert_eq!(parse_size("64K")?, 65536);
+        assert_eq!(parse_size("1.5 MiB")?, 1572864);
+        assert!(parse_size("10XB").is_err());
+        Ok(())
+    }
+}
@@ -1,11 +1,15 @@
+use crate::cli::verify_input_file;
+use crate::process::PassphraseOptions;
 use crate::CmdExecutor;
 use clap::Parser;
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 
 #[derive(Debug, Parser, Serialize, Deserialize)]
 pub struct GenPassOpts {
-    #[arg(short, long, default_value_t = 16)]
-    pub length: u8,
+    /// Password length, at most 4096
+    #[arg(short, long, default_value_t = 16, conflicts_with = "words")]
+    pub length: usize,
 
     #[arg(long, default_value_t = false)]
     pub no_upper: bool,
@@ -18,10 +22,78 @@ pub struct GenPassOpts {
 
     #[arg(long, default_value_t = false)]
     pub no_symbol: bool,
+
+    /// Generate a passphrase of this many words instead of a password
+    #[arg(short, long)]
+    pub words: Option<usize>,
+
+    /// Separator between the words of a passphrase, must not be empty
+    #[arg(long, default_value = "-", requires = "words")]
+    pub separator: String,
+
+    /// Case of the words: lower, upper, title or random
+    #[arg(long, value_parser = parse_word_case, default_value = "lower", requires = "words")]
+    pub case: WordCase,
+
+    /// Append a random digit to one of the words
+    #[arg(long, default_value_t = false, requires = "words")]
+    pub digit: bool,
+
+    /// Append a random symbol to one of the words
+    #[arg(long, default_value_t = false, requires = "words")]
+    pub symbol: bool,
+
+    /// Wordlist file with one word per line, EFF diceware lists work as is
+    #[arg(long, value_parser = verify_input_file, requires = "words")]
+    pub wordlist: Option<String>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WordCase {
+    Lower,
+    Upper,
+    /// First letter uppercase
+    Title,
+    /// Every word entirely upper- or lowercase at random, one extra bit per word
+    Random,
+}
+
+fn parse_word_case(case: &str) -> Result<WordCase, anyhow::Error> {
+    case.parse()
+}
+
+impl FromStr for WordCase {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "lower" => Ok(WordCase::Lower),
+            "upper" => Ok(WordCase::Upper),
+            "title" | "capitalize" => Ok(WordCase::Title),
+            "random" => Ok(WordCase::Random),
+            _ => Err(anyhow::anyhow!("Unsupported case {:?}", value)),
+        }
+    }
 }
 
 impl CmdExecutor for GenPassOpts {
     async fn execute(self) -> anyhow::Result<()> {
+        if let Some(words) = self.words {
+            let opts = PassphraseOptions {
+                words,
+                separator: self.separator,
+                case: self.case,
+                digit: self.digit,
+                symbol: self.symbol,
+                wordlist: self.wordlist,
+            };
+            let pass = crate::process::process_passphrase(&opts)?;
+            println!("{}", pass.phrase);
+            eprintln!("Strength: {}", zxcvbn::zxcvbn(&pass.phrase, &[]).score());
+            eprintln!("Entropy: {:.2} bits", pass.entropy);
+            return Ok(());
+        }
+
         let pass = crate::process::process_genpass(
             self.length,
             self.no_upper,
@@ -29,8 +101,16 @@ impl CmdExecutor for GenPassOpts {
             self.no_number,
             self.no_symbol,
         )?;
+        let entropy = crate::process::genpass_entropy(
+            self.length,
+            self.no_upper,
+            self.no_lower,
+            self.no_number,
+            self.no_symbol,
+        )?;
         println!("{}", pass);
         eprintln!("Strength: {}", zxcvbn::zxcvbn(&pass, &[]).score());
+        eprintln!("Entropy: {:.2} bits", entropy);
         Ok(())
     }
 }
@@ -26,7 +26,7 @@ pub struct Opts {
 #[derive(Debug, Parser, Serialize, Deserialize)]
 pub enum SubCommand {
     #[command(about = "Show CSV, or Convert CSV to other formats")]
-    Csv(CsvOpts),
+    Csv(Box<CsvOpts>),
     #[command(name = "genpass", about = "Generate a random password")]
     GenPass(GenPassOpts),
     #[command(subcommand, about = "Encode or decode base64")]
@@ -19,3 +19,9 @@ pub use utils::*;
 pub trait CmdExecutor {
     async fn execute(self) -> anyhow::Result<()>;
 }
+
+impl<T: CmdExecutor> CmdExecutor for Box<T> {
+    async fn execute(self) -> anyhow::Result<()> {
+        (*self).execute().await
+    }
+}
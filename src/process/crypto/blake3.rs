@@ -14,9 +14,18 @@ impl Blake3 {
     }
 
     pub fn try_new(key: impl AsRef<[u8]>) -> anyhow::Result<Self> {
-        let key: [u8; 32] = key.as_ref()[..32].try_into()?;
+        let key = key
+            .as_ref()
+            .get(..32)
+            .ok_or_else(|| anyhow::anyhow!("Key must be at least 32 bytes"))?;
+        let key: [u8; 32] = key.try_into()?;
         Ok(Self::new(key))
     }
+
+    /// Keyed hash of the data
+    pub fn hash(&self, data: &[u8]) -> [u8; 32] {
+        *blake3::keyed_hash(&self.key, data).as_bytes()
+    }
 }
 
 impl KeyLoader for Blake3 {
@@ -30,7 +39,7 @@ impl TextSign for Blake3 {
     fn sign(&self, reader: &mut dyn Read) -> anyhow::Result<Vec<u8>> {
         let mut buf: Vec<u8> = Vec::new();
         reader.read_to_end(&mut buf)?;
-        Ok(blake3::keyed_hash(&self.key, &buf).as_bytes().to_vec())
+        Ok(self.hash(&buf).to_vec())
     }
 }
 
@@ -38,9 +47,7 @@ impl TextVerify for Blake3 {
     fn verify(&self, mut reader: impl Read, signature: &[u8]) -> anyhow::Result<bool> {
         let mut buf: Vec<u8> = Vec::new();
         reader.read_to_end(&mut buf)?;
-        let hash = blake3::keyed_hash(&self.key, &buf);
-        let hash = hash.as_bytes();
-        Ok(hash == signature)
+        Ok(self.hash(&buf) == signature)
     }
 }
 
@@ -0,0 +1,408 @@
+use crate::cli::csv::{ColumnType, ColumnTypes, Compression, OutputFormat};
+use crate::process::csv::nested::cell;
+use crate::process::csv::writer::RecordWriter;
+use anyhow::{anyhow, bail, Result};
+use arrow_array::{
+    ArrayRef, BooleanArray, Date32Array, Float64Array, Int64Array, RecordBatch, StringArray,
+    TimestampMicrosecondArray,
+};
+use arrow_ipc::writer::{FileWriter, IpcWriteOptions};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::ZstdLevel;
+use parquet::file::properties::WriterProperties;
+use serde_json::{Map, Value};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+/// Records buffered into one record batch, the schema is derived from the first batch
+const BATCH_SIZE: usize = 8192;
+
+/// Arrow type of an output column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Int,
+    Float,
+    Bool,
+    Text,
+    Date,
+    Timestamp,
+}
+
+impl Kind {
+    fn data_type(&self) -> DataType {
+        match self {
+            Kind::Int => DataType::Int64,
+            Kind::Float => DataType::Float64,
+            Kind::Bool => DataType::Boolean,
+            Kind::Text => DataType::Utf8,
+            Kind::Date => DataType::Date32,
+            Kind::Timestamp => DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+        }
+    }
+}
+
+/// Writes records as Parquet or Arrow IPC file in batches.
+/// Column types come from the declared or inferred CSV types, or the values of the first batch.
+pub struct ColumnarWriter {
+    format: OutputFormat,
+    compression: Option<Compression>,
+    declared: ColumnTypes,
+    output: Box<dyn Write>,
+    rows: Vec<Map<String, Value>>,
+    columns: Vec<(String, Kind)>,
+    file: Option<FileSink>,
+}
+
+enum FileSink {
+    Parquet(ArrowWriter<SharedSink>, SharedSink),
+    Arrow(FileWriter<Box<dyn Write>>),
+}
+
+/// ArrowWriter requires a `Send` writer, the encoded bytes are moved to the output after every batch
+#[derive(Clone, Default)]
+struct SharedSink(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedSink {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SharedSink {
+    fn drain_to(&self, output: &mut dyn Write) -> Result<()> {
+        let bytes = std::mem::take(&mut *self.0.lock().unwrap());
+        output.write_all(&bytes)?;
+        Ok(())
+    }
+}
+
+impl ColumnarWriter {
+    pub fn new(
+        format: OutputFormat,
+        output: Box<dyn Write>,
+        declared: ColumnTypes,
+        compression: Option<Compression>,
+    ) -> Self {
+        Self {
+            format,
+            compression,
+            declared,
+            output,
+            rows: Vec::with_capacity(BATCH_SIZE),
+            columns: Vec::new(),
+            file: None,
+        }
+    }
+
+    fn schema(&self) -> SchemaRef {
+        let fields: Vec<Field> = self
+            .columns
+            .iter()
+            .map(|(name, kind)| Field::new(name, kind.data_type(), true))
+            .collect();
+        Arc::new(Schema::new(fields))
+    }
+
+    fn open(&mut self) -> Result<()> {
+        // 以第一批记录中出现的字段作为列
+        for row in &self.rows {
+            for key in row.keys() {
+                if !self.columns.iter().any(|(name, _)| name == key) {
+                    self.columns.push((key.clone(), Kind::Text));
+                }
+            }
+        }
+        for i in 0..self.columns.len() {
+            let name = &self.columns[i].0;
+            let values = self.rows.iter().filter_map(|row| row.get(name));
+            self.columns[i].1 = column_kind(self.declared.get(name), values);
+        }
+
+        let schema = self.schema();
+        let file = match self.format {
+            OutputFormat::Parquet => {
+                let compression = match self.compression.unwrap_or(Compression::Snappy) {
+                    Compression::None => parquet::basic::Compression::UNCOMPRESSED,
+                    Compression::Snappy => parquet::basic::Compression::SNAPPY,
+                    Compression::Zstd => parquet::basic::Compression::ZSTD(ZstdLevel::default()),
+                };
+                let props = WriterProperties::builder()
+                    .set_compression(compression)
+                    .build();
+                let sink = SharedSink::default();
+                FileSink::Parquet(
+                    ArrowWriter::try_new(sink.clone(), schema, Some(props))?,
+                    sink,
+                )
+            }
+            _ => {
+                let compression = match self.compression.unwrap_or(Compression::None) {
+                    Compression::None => None,
+                    Compression::Zstd => Some(arrow_ipc::CompressionType::ZSTD),
+                    Compression::Snappy => bail!("Arrow IPC files only support zstd compression"),
+                };
+                let options = IpcWriteOptions::default().try_with_compression(compression)?;
+                let output = std::mem::replace(&mut self.output, Box::new(std::io::sink()));
+                FileSink::Arrow(FileWriter::try_new_with_options(output, &schema, options)?)
+            }
+        };
+        self.file = Some(file);
+        Ok(())
+    }
+
+    fn flush_batch(&mut self) -> Result<()> {
+        if self.file.is_none() {
+            self.open()?;
+        }
+        let rows = std::mem::take(&mut self.rows);
+        if let Some((key, _)) = rows
+            .iter()
+            .flat_map(|row| row.iter())
+            .find(|(key, _)| !self.columns.iter().any(|(name, _)| name == *key))
+        {
+            bail!(
+                "Column {:?} is missing from the schema derived from the first {} records",
+                key,
+                BATCH_SIZE
+            );
+        }
+        let arrays = self
+            .columns
+            .iter()
+            .map(|(name, kind)| column_array(name, *kind, &rows))
+            .collect::<Result<Vec<_>>>()?;
+        let batch = RecordBatch::try_new(self.schema(), arrays)?;
+
+        match self.file.as_mut() {
+            Some(FileSink::Parquet(writer, sink)) => {
+                writer.write(&batch)?;
+                sink.drain_to(&mut self.output)?;
+            }
+            Some(FileSink::Arrow(writer)) => writer.write(&batch)?,
+            None => {}
+        }
+        Ok(())
+    }
+}
+
+impl RecordWriter for ColumnarWriter {
+    fn write(&mut self, record: &Value) -> Result<()> {
+        let row = match record {
+            Value::Object(object) => object.clone(),
+            _ => Map::from_iter([("value".to_string(), record.clone())]),
+        };
+        self.rows.push(row);
+        if self.rows.len() >= BATCH_SIZE {
+            self.flush_batch()?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if !self.rows.is_empty() || self.file.is_none() {
+            self.flush_batch()?;
+        }
+        match self.file.take() {
+            Some(FileSink::Parquet(writer, sink)) => {
+                writer.close()?;
+                sink.drain_to(&mut self.output)?;
+                self.output.flush()?;
+            }
+            Some(FileSink::Arrow(mut writer)) => {
+                writer.finish()?;
+                writer.get_mut().flush()?;
+            }
+            None => {}
+        }
+        Ok(())
+    }
+}
+
+/// Declared types win, dates become Date32 or a UTC timestamp depending on their values
+fn column_kind<'a>(declared: Option<&ColumnType>, values: impl Iterator<Item = &'a Value>) -> Kind {
+    let values: Vec<&Value> = values.filter(|v| !v.is_null()).collect();
+    let all = |f: fn(&Value) -> bool| values.iter().all(|v| f(v));
+    match declared {
+        Some(ColumnType::Integer) => Kind::Int,
+        Some(ColumnType::Float) => Kind::Float,
+        Some(ColumnType::Boolean) => Kind::Bool,
+        Some(ColumnType::String) | Some(ColumnType::Null) => Kind::Text,
+        Some(ColumnType::Date(_)) if all(|v| v.as_str().and_then(parse_date).is_some()) => {
+            Kind::Date
+        }
+        Some(ColumnType::Date(_)) => Kind::Timestamp,
+        // 没有声明类型的列（如映射后的新列）按第一批的值推断
+        None if values.is_empty() => Kind::Text,
+        None if all(|v| v.is_i64()) => Kind::Int,
+        None if all(Value::is_number) => Kind::Float,
+        None if all(Value::is_boolean) => Kind::Bool,
+        None => Kind::Text,
+    }
+}
+
+fn column_array(name: &str, kind: Kind, rows: &[Map<String, Value>]) -> Result<ArrayRef> {
+    let values = rows
+        .iter()
+        .map(|row| row.get(name).filter(|v| !v.is_null()));
+    let invalid = |value: &Value| anyhow!("Column {:?}: invalid {:?} value {}", name, kind, value);
+    let array: ArrayRef = match kind {
+        Kind::Int => Arc::new(Int64Array::from(
+            values
+                .map(|v| v.map(|v| v.as_i64().ok_or_else(|| invalid(v))).transpose())
+                .collect::<Result<Vec<_>>>()?,
+        )),
+        Kind::Float => Arc::new(Float64Array::from(
+            values
+                .map(|v| v.map(|v| v.as_f64().ok_or_else(|| invalid(v))).transpose())
+                .collect::<Result<Vec<_>>>()?,
+        )),
+        Kind::Bool => Arc::new(BooleanArray::from(
+            values
+                .map(|v| v.map(|v| v.as_bool().ok_or_else(|| invalid(v))).transpose())
+                .collect::<Result<Vec<_>>>()?,
+        )),
+        Kind::Text => Arc::new(StringArray::from(
+            values.map(|v| v.map(cell)).collect::<Vec<_>>(),
+        )),
+        Kind::Date => Arc::new(Date32Array::from(
+            values
+                .map(|v| {
+                    v.map(|v| v.as_str().and_then(parse_date).ok_or_else(|| invalid(v)))
+                        .transpose()
+                })
+                .collect::<Result<Vec<_>>>()?,
+        )),
+        Kind::Timestamp => Arc::new(
+            TimestampMicrosecondArray::from(
+                values
+                    .map(|v| {
+                        v.map(|v| {
+                            v.as_str()
+                                .and_then(parse_timestamp)
+                                .ok_or_else(|| invalid(v))
+                        })
+                        .transpose()
+                    })
+                    .collect::<Result<Vec<_>>>()?,
+            )
+            .with_timezone("UTC"),
+        ),
+    };
+    Ok(array)
+}
+
+/// Days since the Unix epoch
+fn parse_date(value: &str) -> Option<i32> {
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+    let epoch = NaiveDate::from_ymd_opt(1970, 1, 1)?;
+    i32::try_from((date - epoch).num_days()).ok()
+}
+
+/// Microseconds since the Unix epoch, datetimes without an offset are taken as UTC
+fn parse_timestamp(value: &str) -> Option<i64> {
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(value) {
+        return Some(datetime.timestamp_micros());
+    }
+    if let Ok(datetime) = NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S") {
+        return Some(datetime.and_utc().timestamp_micros());
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|datetime| datetime.and_utc().timestamp_micros())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::csv::writer::tests::SharedBuf;
+    use arrow_array::Array;
+    use arrow_ipc::reader::FileReader;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use serde_json::json;
+    use std::fs;
+    use std::io::Cursor;
+
+    fn write_all(format: OutputFormat, compression: Option<Compression>) -> Result<Vec<u8>> {
+        let buf = SharedBuf::default();
+        let declared: ColumnTypes = "Kit=int,DOB=date:%b %d, %Y".parse()?;
+        let mut writer = ColumnarWriter::new(format, Box::new(buf.clone()), declared, compression);
+        writer.write(&json!({"Name": "Buffon", "Kit": null, "DOB": "1978-01-28", "Rating": 7}))?;
+        writer.write(&json!({"Name": "Chiellini", "Kit": 3, "DOB": null, "Rating": 6.5}))?;
+        writer.finish()?;
+        let bytes = buf.0.borrow().clone();
+        Ok(bytes)
+    }
+
+    fn check(batch: &RecordBatch) {
+        let schema = batch.schema();
+        let types: Vec<&DataType> = schema.fields().iter().map(|f| f.data_type()).collect();
+        assert_eq!(
+            types,
+            [
+                &DataType::Utf8,
+                &DataType::Int64,
+                &DataType::Date32,
+                &DataType::Float64
+            ]
+        );
+        assert_eq!(batch.num_rows(), 2);
+        let kit = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert!(kit.is_null(0));
+        assert_eq!(kit.value(1), 3);
+        let dob = batch
+            .column(2)
+            .as_any()
+            .downcast_ref::<Date32Array>()
+            .unwrap();
+        assert_eq!(dob.value(0), 2949);
+    }
+
+    #[test]
+    fn test_parquet_round_trip() -> Result<()> {
+        for compression in [None, Some(Compression::Zstd), Some(Compression::None)] {
+            let bytes = write_all(OutputFormat::Parquet, compression)?;
+            let path = std::env::temp_dir().join(format!("rcli-{}.parquet", std::process::id()));
+            fs::write(&path, bytes)?;
+            let reader =
+                ParquetRecordBatchReaderBuilder::try_new(fs::File::open(&path)?)?.build()?;
+            let batches = reader.collect::<Result<Vec<_>, _>>()?;
+            check(&batches[0]);
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_arrow_round_trip() -> Result<()> {
+        let bytes = write_all(OutputFormat::Arrow, Some(Compression::Zstd))?;
+        let reader = FileReader::try_new(Cursor::new(bytes), None)?;
+        let batches = reader.collect::<Result<Vec<_>, _>>()?;
+        check(&batches[0]);
+
+        assert!(write_all(OutputFormat::Arrow, Some(Compression::Snappy)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_column_kind() {
+        let values = [json!(1), json!(2.5)];
+        assert_eq!(column_kind(None, values.iter()), Kind::Float);
+        let values = [json!(null)];
+        assert_eq!(column_kind(None, values.iter()), Kind::Text);
+        let values = [json!("2024-05-01T10:00:00")];
+        let date = ColumnType::Date(None);
+        assert_eq!(column_kind(Some(&date), values.iter()), Kind::Timestamp);
+    }
+}
@@ -0,0 +1,132 @@
+use crate::cli::csv::{ColumnTypes, OutputFormat};
+use crate::process::csv::dialect::CsvDialect;
+use crate::process::csv::filter::{FilterOptions, RecordFilter};
+use crate::process::csv::mapping::{Mapping, RecordMapper};
+use crate::process::csv::nested::unflatten;
+use crate::process::csv::parallel::ParallelReader;
+use crate::process::csv::reader::TypedReader;
+use crate::process::csv::transform::{TransformOptions, Transformer};
+use crate::process::csv::writer::{record_writer, WriterOptions};
+use anyhow::Result;
+use serde_json::Value;
+use std::fs::File;
+use std::io::BufWriter;
+use tracing::info;
+
+pub struct ConvertOptions {
+    pub format: OutputFormat,
+    pub dialect: CsvDialect,
+    pub infer: bool,
+    pub types: ColumnTypes,
+    pub transform: TransformOptions,
+    pub filter: FilterOptions,
+    pub mapping: Option<Mapping>,
+    /// Separator of nested keys in the headers
+    pub unflatten: Option<String>,
+    pub writer: WriterOptions,
+    /// Parse the input on this many threads, 0 uses one per CPU. Without `--sort-by` and
+    /// `--unique` the workers also filter and map the records, the output is always
+    /// serialized on the calling thread.
+    pub threads: Option<usize>,
+}
+
+pub fn process_csv(input: &str, output: String, opts: &ConvertOptions) -> Result<()> {
+    let types = match &opts.mapping {
+        Some(mapping) => mapping.types(&opts.types),
+        None => opts.types.clone(),
+    };
+    if let Some(threads) = opts.threads {
+        if ParallelReader::supports(input) {
+            let reader = ParallelReader::open(input, &opts.dialect, opts.infer, &types, threads)?;
+            // 转换在过滤之前执行，过滤和映射使用转换后的列名
+            let transformer = Transformer::new(reader.headers(), &opts.transform)?;
+            let headers = transformer.headers().to_vec();
+            let columns = transformer.column_types(reader.types());
+            let filter = RecordFilter::new(&headers, &opts.filter)?;
+            let reshape = Reshape::new(&headers, opts)?;
+            if filter.is_streaming() {
+                // 逐条处理的步骤都在工作线程上执行，主线程只负责序列化
+                let records = reader.records(move |record| {
+                    match filter.apply_record(transformer.apply(record)?) {
+                        Some(record) => reshape.apply(record).map(Some),
+                        None => Ok(None),
+                    }
+                });
+                return write_records(records, columns, output, opts);
+            }
+            let records = reader.records(move |record| transformer.apply(record).map(Some));
+            let records = filter
+                .apply(records)?
+                .map(move |record| reshape.apply(record?));
+            return write_records(records, columns, output, opts);
+        }
+        info!(
+            "Reading {:?} sequentially, only CSV files can be split into chunks",
+            input
+        );
+    }
+
+    let reader = TypedReader::open(input, &opts.dialect, opts.infer, &types)?;
+    let transformer = Transformer::new(reader.headers(), &opts.transform)?;
+    let headers = transformer.headers().to_vec();
+    let columns = transformer.column_types(reader.types());
+    let filter = RecordFilter::new(&headers, &opts.filter)?;
+    let reshape = Reshape::new(&headers, opts)?;
+    let records = reader
+        .records()
+        .map(move |record| transformer.apply(record?));
+    let records = filter
+        .apply(records)?
+        .map(move |record| reshape.apply(record?));
+    write_records(records, columns, output, opts)
+}
+
+/// Mapping and unflattening of the filtered records
+struct Reshape {
+    mapper: Option<RecordMapper>,
+    unflatten: Option<String>,
+}
+
+impl Reshape {
+    fn new(headers: &[String], opts: &ConvertOptions) -> Result<Self> {
+        let mapper = opts
+            .mapping
+            .clone()
+            .map(|mapping| RecordMapper::new(headers, mapping))
+            .transpose()?;
+        Ok(Self {
+            mapper,
+            unflatten: opts.unflatten.clone(),
+        })
+    }
+
+    fn apply(&self, record: Value) -> Result<Value> {
+        let record = match &self.mapper {
+            Some(mapper) => mapper.apply(record)?,
+            None => record,
+        };
+        match &self.unflatten {
+            Some(separator) => unflatten(record, separator),
+            None => Ok(record),
+        }
+    }
+}
+
+fn write_records(
+    records: impl Iterator<Item = Result<Value>>,
+    columns: ColumnTypes,
+    output: String,
+    opts: &ConvertOptions,
+) -> Result<()> {
+    // 列式格式的schema使用推断或声明的列类型
+    let writer_opts = WriterOptions {
+        columns,
+        ..opts.writer.clone()
+    };
+    let file = BufWriter::new(File::create(output)?);
+    let mut writer = record_writer(opts.format, Box::new(file), &writer_opts);
+    for record in records {
+        writer.write(&record?)?;
+    }
+    writer.finish()
+}
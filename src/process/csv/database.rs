@@ -0,0 +1,194 @@
+use crate::cli::csv::{ColumnTypes, IfExists, OutputFormat};
+use crate::process::csv::dialect::CsvDialect;
+use crate::process::csv::reader::TypedReader;
+use crate::process::csv::sqlite::{
+    create_table, insert_records, query_records, quote_ident, table_name,
+};
+use crate::process::csv::writer::{record_writer, WriterOptions};
+use anyhow::{bail, Result};
+use rusqlite::{Connection, OpenFlags};
+use std::io::Write;
+
+pub struct ToSqliteOptions {
+    /// Table name, derived from the input file name when missing
+    pub table: Option<String>,
+    pub dialect: CsvDialect,
+    pub types: ColumnTypes,
+    /// Comma separated columns of every index to create
+    pub indexes: Vec<String>,
+    pub if_exists: IfExists,
+}
+
+/// Load a CSV file into a table of a SQLite database, the database is created when missing.
+/// The import is atomic. Returns the table name and the number of inserted rows.
+pub fn process_to_sqlite(input: &str, db: &str, opts: &ToSqliteOptions) -> Result<(String, usize)> {
+    let table = opts.table.clone().unwrap_or_else(|| table_name(input));
+    let reader = TypedReader::open(input, &opts.dialect, true, &opts.types)?;
+    let headers = reader.headers().to_vec();
+    let indexes = opts
+        .indexes
+        .iter()
+        .map(|index| {
+            let columns: Vec<String> = index.split(',').map(|c| c.trim().to_string()).collect();
+            match columns.iter().find(|c| !headers.contains(c)) {
+                Some(column) => bail!("Column {:?} not found", column),
+                None => Ok(columns),
+            }
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut conn = Connection::open(db)?;
+    // 删表、建表、插入和建索引在同一个事务中，任何一行出错时数据库保持原样
+    let tx = conn.transaction()?;
+    let exists = tx
+        .prepare("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1")?
+        .exists([&table])?;
+    match (exists, opts.if_exists) {
+        (true, IfExists::Fail) => bail!(
+            "Table {:?} already exists in {:?}, use --if-exists replace or append",
+            table,
+            db
+        ),
+        (true, IfExists::Replace) => {
+            tx.execute(&format!("DROP TABLE {}", quote_ident(&table)), [])?;
+            create_table(&tx, &table, &headers, reader.types())?;
+        }
+        (true, IfExists::Append) => {}
+        (false, _) => create_table(&tx, &table, &headers, reader.types())?,
+    }
+
+    let count = insert_records(&tx, &table, &headers, reader.records())?;
+    // 索引在插入完成后创建，比逐行维护索引更快
+    for columns in indexes {
+        let name = format!("idx_{}_{}", table, columns.join("_"))
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '_' })
+            .collect::<String>();
+        let columns = columns
+            .iter()
+            .map(|c| quote_ident(c))
+            .collect::<Vec<_>>()
+            .join(", ");
+        tx.execute(
+            &format!(
+                "CREATE INDEX IF NOT EXISTS {} ON {} ({})",
+                quote_ident(&name),
+                quote_ident(&table),
+                columns
+            ),
+            [],
+        )?;
+    }
+    tx.commit()?;
+    Ok((table, count))
+}
+
+/// Run a query against a SQLite database, the database is opened read-only
+pub fn process_from_sqlite(
+    db: &str,
+    sql: &str,
+    output: Box<dyn Write>,
+    format: OutputFormat,
+) -> Result<()> {
+    let conn = Connection::open_with_flags(db, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let mut writer = record_writer(format, output, &WriterOptions::default());
+    query_records(&conn, sql, |record| writer.write(&record))?;
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::csv::writer::tests::SharedBuf;
+    use serde_json::{json, Value};
+    use std::fs;
+
+    fn options(if_exists: IfExists) -> ToSqliteOptions {
+        ToSqliteOptions {
+            table: Some("players".to_string()),
+            dialect: CsvDialect::default(),
+            types: ColumnTypes::default(),
+            indexes: vec!["Nationality, Position".to_string()],
+            if_exists,
+        }
+    }
+
+    fn query(db: &str, sql: &str) -> Result<Value> {
+        let buf = SharedBuf::default();
+        process_from_sqlite(db, sql, Box::new(buf.clone()), OutputFormat::Json)?;
+        let ret = serde_json::from_slice(&buf.0.borrow())?;
+        Ok(ret)
+    }
+
+    #[test]
+    fn test_sqlite_round_trip() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("rcli-{}.db", std::process::id()));
+        let db = path.to_string_lossy().to_string();
+        let _ = fs::remove_file(&path);
+
+        let input = "assets/juventus.csv";
+        let ret = process_to_sqlite(input, &db, &options(IfExists::Fail))?;
+        assert_eq!(ret, ("players".to_string(), 27));
+        assert!(process_to_sqlite(input, &db, &options(IfExists::Fail)).is_err());
+        process_to_sqlite(input, &db, &options(IfExists::Append))?;
+        process_to_sqlite(input, &db, &options(IfExists::Replace))?;
+
+        let ret = query(
+            &db,
+            "SELECT name, sql FROM sqlite_master WHERE type = 'index' AND tbl_name = 'players'",
+        )?;
+        assert_eq!(
+            ret,
+            json!([{
+                "name": "idx_players_Nationality_Position",
+                "sql": "CREATE INDEX \"idx_players_Nationality_Position\" ON \"players\" (\"Nationality\", \"Position\")"
+            }])
+        );
+        let ret = query(
+            &db,
+            "SELECT Name, \"Kit Number\" FROM players WHERE \"Kit Number\" < 2",
+        )?;
+        assert_eq!(ret, json!([{"Name": "Wojciech Szczesny", "Kit Number": 1}]));
+        assert!(process_from_sqlite(
+            &db,
+            "DELETE FROM players",
+            Box::new(SharedBuf::default()),
+            OutputFormat::Csv
+        )
+        .is_err());
+
+        fs::remove_file(path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_sqlite_import_is_atomic() -> Result<()> {
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("rcli-atomic-{}.db", std::process::id()));
+        let csv = dir.join(format!("rcli-atomic-{}.csv", std::process::id()));
+        let db = path.to_string_lossy().to_string();
+        let _ = fs::remove_file(&path);
+
+        fs::write(&csv, "id,age\n1,30\n2,31\n")?;
+        let input = csv.to_string_lossy().to_string();
+        let types: ColumnTypes = "age=int".parse()?;
+        let opts = |if_exists| ToSqliteOptions {
+            table: Some("people".to_string()),
+            types: types.clone(),
+            indexes: vec![],
+            ..options(if_exists)
+        };
+        process_to_sqlite(&input, &db, &opts(IfExists::Fail))?;
+
+        // 第三行的 age 不是整数，替换失败后原表和数据保持不变
+        fs::write(&csv, "id,age\n3,40\n4,41\n5,unknown\n6,42\n")?;
+        assert!(process_to_sqlite(&input, &db, &opts(IfExists::Replace)).is_err());
+        assert!(process_to_sqlite(&input, &db, &opts(IfExists::Append)).is_err());
+        let ret = query(&db, "SELECT id, age FROM people")?;
+        assert_eq!(ret, json!([{"id": 1, "age": 30}, {"id": 2, "age": 31}]));
+
+        fs::remove_file(path)?;
+        fs::remove_file(csv)?;
+        Ok(())
+    }
+}
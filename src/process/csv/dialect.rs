@@ -0,0 +1,285 @@
+use crate::get_reader;
+use crate::process::csv::encoding::decode_reader;
+use crate::process::csv::workbook::{is_workbook, workbook_reader};
+use anyhow::Result;
+use csv::{Reader, ReaderBuilder, StringRecord, WriterBuilder};
+use encoding_rs::Encoding;
+use std::io::{BufRead, BufReader, Cursor, Read};
+use tracing::info;
+
+const DELIMITERS: [u8; 4] = [b',', b'\t', b';', b'|'];
+const QUOTES: [u8; 2] = [b'"', b'\''];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvDialect {
+    pub delimiter: u8,
+    pub has_header: bool,
+    pub quote: u8,
+    /// Escape character inside quoted fields, `None` means quotes are escaped by doubling them
+    pub escape: Option<u8>,
+    pub comment: Option<u8>,
+    pub flexible: bool,
+    /// Number of lines to inspect for automatic dialect detection
+    pub sniff: Option<usize>,
+    /// Character encoding of the input, detected when `None`
+    pub encoding: Option<&'static Encoding>,
+    /// Worksheet name or index when the input is a workbook
+    pub sheet: Option<String>,
+    /// Cell range of the worksheet, e.g. `A1:D10`
+    pub range: Option<String>,
+}
+
+impl Default for CsvDialect {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            has_header: true,
+            quote: b'"',
+            escape: None,
+            comment: None,
+            flexible: false,
+            sniff: None,
+            encoding: None,
+            sheet: None,
+            range: None,
+        }
+    }
+}
+
+impl CsvDialect {
+    pub fn builder(&self) -> ReaderBuilder {
+        let mut builder = ReaderBuilder::new();
+        builder
+            .delimiter(self.delimiter)
+            .has_headers(self.has_header)
+            .quote(self.quote)
+            .double_quote(self.escape.is_none())
+            .escape(self.escape)
+            .comment(self.comment)
+            .flexible(self.flexible);
+        builder
+    }
+
+    /// Writer producing CSV in the same dialect
+    pub fn writer_builder(&self) -> WriterBuilder {
+        let mut builder = WriterBuilder::new();
+        builder
+            .delimiter(self.delimiter)
+            .quote(self.quote)
+            .double_quote(self.escape.is_none())
+            .escape(self.escape.unwrap_or(b'\\'));
+        builder
+    }
+
+    /// Detect delimiter, quote character and header presence from a sample of the input
+    pub fn sniff(&mut self, sample: &[u8]) {
+        let mut best: Option<(usize, usize, u8, u8)> = None;
+        for quote in QUOTES {
+            for delimiter in DELIMITERS {
+                let counts = self.field_counts(sample, delimiter, quote);
+                let Some((consistent, width)) = score(&counts) else {
+                    continue;
+                };
+                // 优先一致的行数，其次是列数，并列时保留候选表中靠前的组合
+                if best.is_none_or(|(c, w, _, _)| (consistent, width) > (c, w)) {
+                    best = Some((consistent, width, delimiter, quote));
+                }
+            }
+        }
+
+        if let Some((_, _, delimiter, quote)) = best {
+            self.delimiter = delimiter;
+            self.quote = quote;
+        }
+        self.has_header = self.detect_header(sample);
+    }
+
+    fn sample_reader<'a>(&self, sample: &'a [u8], delimiter: u8, quote: u8) -> Reader<&'a [u8]> {
+        let mut dialect = self.clone();
+        dialect.delimiter = delimiter;
+        dialect.quote = quote;
+        dialect.has_header = false;
+        dialect.flexible = true;
+        dialect.builder().from_reader(sample)
+    }
+
+    fn field_counts(&self, sample: &[u8], delimiter: u8, quote: u8) -> Vec<usize> {
+        // 采样的最后一行可能被截断，解析出错时直接丢弃剩余部分
+        self.sample_reader(sample, delimiter, quote)
+            .records()
+            .map_while(|record| record.ok())
+            .map(|record| record.len())
+            .collect()
+    }
+
+    fn detect_header(&self, sample: &[u8]) -> bool {
+        let records: Vec<StringRecord> = self
+            .sample_reader(sample, self.delimiter, self.quote)
+            .records()
+            .map_while(|record| record.ok())
+            .collect();
+        let Some((first, rest)) = records.split_first() else {
+            return self.has_header;
+        };
+        if rest.is_empty() {
+            return self.has_header;
+        }
+
+        let mut votes = 0i32;
+        for (i, title) in first.iter().enumerate() {
+            let column: Vec<&str> = rest.iter().filter_map(|r| r.get(i)).collect();
+            if column.is_empty() {
+                continue;
+            }
+            if column.iter().all(|cell| cell.trim().parse::<f64>().is_ok()) {
+                if title.trim().parse::<f64>().is_ok() {
+                    votes -= 1;
+                } else {
+                    votes += 1;
+                }
+            } else {
+                let len = column[0].chars().count();
+                if column.iter().all(|cell| cell.chars().count() == len) {
+                    if title.chars().count() == len {
+                        votes -= 1;
+                    } else {
+                        votes += 1;
+                    }
+                }
+            }
+        }
+        votes >= 0
+    }
+}
+
+/// Returns how many records share the most common field count, together with that count
+fn score(counts: &[usize]) -> Option<(usize, usize)> {
+    let mut modes: Vec<(usize, usize)> = Vec::new();
+    for &count in counts {
+        match modes.iter_mut().find(|(width, _)| *width == count) {
+            Some((_, n)) => *n += 1,
+            None => modes.push((count, 1)),
+        }
+    }
+    modes
+        .into_iter()
+        .filter(|(width, _)| *width > 1)
+        .max_by_key(|&(width, n)| (n, width))
+        .map(|(width, n)| (n, width))
+}
+
+/// Open the input with the given dialect, sniffing it first when requested.
+/// Workbooks are read from the selected sheet instead.
+pub fn csv_reader(input: &str, dialect: &CsvDialect) -> Result<Reader<Box<dyn Read>>> {
+    Ok(sniffed_csv_reader(input, dialect)?.0)
+}
+
+/// Like `csv_reader`, also returns the dialect after sniffing for writing the output in kind
+pub fn sniffed_csv_reader(
+    input: &str,
+    dialect: &CsvDialect,
+) -> Result<(Reader<Box<dyn Read>>, CsvDialect)> {
+    if is_workbook(input) {
+        let reader = workbook_reader(input, dialect)?;
+        return Ok((dialect.builder().from_reader(reader), dialect.clone()));
+    }
+    let (reader, dialect) = decoded_input(input, dialect)?;
+    Ok((dialect.builder().from_reader(reader), dialect))
+}
+
+/// The input transcoded to UTF-8, together with the dialect after sniffing
+pub fn decoded_input(input: &str, dialect: &CsvDialect) -> Result<(Box<dyn Read>, CsvDialect)> {
+    let reader = decode_reader(get_reader(input)?, dialect.encoding)?;
+    let Some(lines) = dialect.sniff else {
+        return Ok((reader, dialect.clone()));
+    };
+
+    // 先读取前N行用于探测，再与剩余内容拼接，这样stdin也可以探测
+    let mut reader = BufReader::new(reader);
+    let mut sample = Vec::new();
+    for _ in 0..lines {
+        if reader.read_until(b'\n', &mut sample)? == 0 {
+            break;
+        }
+    }
+
+    let mut dialect = dialect.clone();
+    dialect.sniff(&sample);
+    info!(
+        "Sniffed dialect: delimiter={:?}, quote={:?}, header={}",
+        dialect.delimiter as char, dialect.quote as char, dialect.has_header
+    );
+
+    let reader: Box<dyn Read> = Box::new(Cursor::new(sample).chain(reader));
+    Ok((reader, dialect))
+}
+
+/// Header names of the reader, generated as `column_N` when the input has no header row
+pub fn csv_headers<R: Read>(reader: &mut Reader<R>) -> Result<Vec<String>> {
+    let has_headers = reader.has_headers();
+    let headers = reader.headers()?;
+    if has_headers {
+        // 去掉残留在第一个列名前的BOM
+        Ok(headers
+            .iter()
+            .enumerate()
+            .map(|(i, h)| match i {
+                0 => h.trim_start_matches('\u{feff}').to_string(),
+                _ => h.to_string(),
+            })
+            .collect())
+    } else {
+        Ok((1..=headers.len())
+            .map(|i| format!("column_{}", i))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sniffed(sample: &str) -> CsvDialect {
+        let mut dialect = CsvDialect::default();
+        dialect.sniff(sample.as_bytes());
+        dialect
+    }
+
+    #[test]
+    fn test_sniff_delimiter() {
+        let dialect = sniffed("name\tage\nalice\t30\nbob\t25\n");
+        assert_eq!(dialect.delimiter, b'\t');
+
+        let dialect = sniffed("name;city\nalice;\"Paris; France\"\nbob;Rome\n");
+        assert_eq!(dialect.delimiter, b';');
+
+        let dialect = sniffed("a|b|c\n1|2|3\n4|5|6\n");
+        assert_eq!(dialect.delimiter, b'|');
+    }
+
+    #[test]
+    fn test_sniff_header() -> Result<()> {
+        let sample = std::fs::read("assets/juventus.csv")?;
+        let mut dialect = CsvDialect::default();
+        dialect.sniff(&sample);
+        assert_eq!(dialect.delimiter, b',');
+        assert!(dialect.has_header);
+
+        let dialect = sniffed("alice,30\nbob,25\ncarol,41\n");
+        assert!(!dialect.has_header);
+        Ok(())
+    }
+
+    #[test]
+    fn test_csv_reader_without_header() -> Result<()> {
+        let dialect = CsvDialect {
+            has_header: false,
+            ..Default::default()
+        };
+        let mut reader = csv_reader("assets/juventus.csv", &dialect)?;
+        let headers = csv_headers(&mut reader)?;
+        assert_eq!(headers[0], "column_1");
+        assert_eq!(headers.len(), 5);
+        Ok(())
+    }
+}
@@ -0,0 +1,481 @@
+use crate::cli::csv::DiffFormat;
+use crate::process::csv::dialect::{csv_headers, csv_reader, CsvDialect};
+use anyhow::{bail, Result};
+use colored::Colorize;
+use csv::{Reader, StringRecord};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+pub struct DiffOptions {
+    pub dialect: CsvDialect,
+    /// Key columns identifying a row, rows are compared by position when empty
+    pub keys: Vec<String>,
+    pub format: DiffFormat,
+    /// Color the text report, only when it goes to a terminal
+    pub color: bool,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct DiffReport {
+    /// Columns of the new file
+    #[serde(skip)]
+    pub headers: Vec<String>,
+    pub columns_added: Vec<String>,
+    pub columns_removed: Vec<String>,
+    pub changes: Vec<RowChange>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "change", rename_all = "lowercase")]
+pub enum RowChange {
+    Added {
+        key: Map<String, Value>,
+        line: u64,
+        record: Map<String, Value>,
+    },
+    Removed {
+        key: Map<String, Value>,
+        line: u64,
+        record: Map<String, Value>,
+    },
+    Changed {
+        key: Map<String, Value>,
+        old_line: u64,
+        new_line: u64,
+        columns: Vec<ColumnChange>,
+    },
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ColumnChange {
+    pub column: String,
+    pub old: String,
+    pub new: String,
+}
+
+pub fn process_diff(
+    old: &str,
+    new: &str,
+    mut output: Box<dyn Write>,
+    opts: &DiffOptions,
+) -> Result<()> {
+    if old == "-" && new == "-" {
+        bail!("Only one side of the diff can be read from stdin");
+    }
+    let report = diff(
+        csv_reader(old, &opts.dialect)?,
+        csv_reader(new, &opts.dialect)?,
+        &opts.keys,
+    )?;
+    match opts.format {
+        DiffFormat::Text => {
+            colored::control::set_override(opts.color);
+            write_text(&report, &mut output)?;
+        }
+        DiffFormat::Json => serde_json::to_writer_pretty(&mut output, &report)?,
+        DiffFormat::Csv => write_patch(&report, &opts.keys, &mut output)?,
+    }
+    output.flush()?;
+    Ok(())
+}
+
+struct Side {
+    headers: Vec<String>,
+    /// Records with their line numbers
+    records: Vec<(u64, StringRecord)>,
+}
+
+impl Side {
+    fn load<R: Read>(mut reader: Reader<R>) -> Result<Self> {
+        let headers = csv_headers(&mut reader)?;
+        let records = reader
+            .into_records()
+            .map(|record| {
+                let record = record?;
+                Ok((record.position().map_or(0, |p| p.line()), record))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { headers, records })
+    }
+
+    fn index(&self, column: &str) -> Option<usize> {
+        self.headers.iter().position(|h| h == column)
+    }
+
+    fn object(&self, record: &StringRecord) -> Map<String, Value> {
+        self.headers
+            .iter()
+            .zip(record.iter())
+            .map(|(h, v)| (h.clone(), Value::String(v.to_string())))
+            .collect()
+    }
+}
+
+/// Compare two CSV files by key columns, or by position when no key is given
+pub fn diff<R: Read>(old: Reader<R>, new: Reader<R>, keys: &[String]) -> Result<DiffReport> {
+    let old = Side::load(old)?;
+    let new = Side::load(new)?;
+
+    let mut report = DiffReport {
+        headers: new.headers.clone(),
+        columns_added: new
+            .headers
+            .iter()
+            .filter(|h| !old.headers.contains(h))
+            .cloned()
+            .collect(),
+        columns_removed: old
+            .headers
+            .iter()
+            .filter(|h| !new.headers.contains(h))
+            .cloned()
+            .collect(),
+        changes: Vec::new(),
+    };
+    // 只比较两边都有的列
+    let common: Vec<(String, usize, usize)> = old
+        .headers
+        .iter()
+        .enumerate()
+        .filter_map(|(i, h)| new.index(h).map(|j| (h.clone(), i, j)))
+        .collect();
+    let compare = |old_record: &StringRecord, new_record: &StringRecord| {
+        common
+            .iter()
+            .filter_map(|(column, i, j)| {
+                let before = old_record.get(*i).unwrap_or_default();
+                let after = new_record.get(*j).unwrap_or_default();
+                (before != after).then(|| ColumnChange {
+                    column: column.clone(),
+                    old: before.to_string(),
+                    new: after.to_string(),
+                })
+            })
+            .collect::<Vec<_>>()
+    };
+
+    if keys.is_empty() {
+        let rows = old.records.len().max(new.records.len());
+        for row in 0..rows {
+            let key = Map::from_iter([("row".to_string(), Value::from(row + 1))]);
+            let change = match (old.records.get(row), new.records.get(row)) {
+                (Some((old_line, o)), Some((new_line, n))) => {
+                    let columns = compare(o, n);
+                    if columns.is_empty() {
+                        continue;
+                    }
+                    RowChange::Changed {
+                        key,
+                        old_line: *old_line,
+                        new_line: *new_line,
+                        columns,
+                    }
+                }
+                (None, Some((line, n))) => RowChange::Added {
+                    key,
+                    line: *line,
+                    record: new.object(n),
+                },
+                (Some((line, o)), None) => RowChange::Removed {
+                    key,
+                    line: *line,
+                    record: old.object(o),
+                },
+                (None, None) => unreachable!(),
+            };
+            report.changes.push(change);
+        }
+        return Ok(report);
+    }
+
+    let old_keys = key_indices(&old, keys, "old")?;
+    let new_keys = key_indices(&new, keys, "new")?;
+    let key_of = |record: &StringRecord, indices: &[usize]| {
+        indices
+            .iter()
+            .map(|&i| record.get(i).unwrap_or_default().to_string())
+            .collect::<Vec<_>>()
+    };
+    let key_object = |key: &[String]| {
+        keys.iter()
+            .cloned()
+            .zip(key.iter().map(|k| Value::String(k.clone())))
+            .collect::<Map<_, _>>()
+    };
+
+    let mut index = HashMap::with_capacity(old.records.len());
+    for (i, (line, record)) in old.records.iter().enumerate() {
+        if let Some(first) = index.insert(key_of(record, &old_keys), i) {
+            bail!(
+                "Duplicate key on line {} of the old file, first seen on line {}",
+                line,
+                old.records[first].0
+            );
+        }
+    }
+    let mut seen = vec![false; old.records.len()];
+    let mut new_seen = HashMap::with_capacity(new.records.len());
+    for (new_line, record) in &new.records {
+        let key = key_of(record, &new_keys);
+        if let Some(first) = new_seen.insert(key.clone(), *new_line) {
+            bail!(
+                "Duplicate key on line {} of the new file, first seen on line {}",
+                new_line,
+                first
+            );
+        }
+        match index.get(&key) {
+            Some(&i) => {
+                seen[i] = true;
+                let (old_line, old_record) = &old.records[i];
+                let columns = compare(old_record, record);
+                if !columns.is_empty() {
+                    report.changes.push(RowChange::Changed {
+                        key: key_object(&key),
+                        old_line: *old_line,
+                        new_line: *new_line,
+                        columns,
+                    });
+                }
+            }
+            None => report.changes.push(RowChange::Added {
+                key: key_object(&key),
+                line: *new_line,
+                record: new.object(record),
+            }),
+        }
+    }
+    for ((line, record), _) in old.records.iter().zip(&seen).filter(|(_, &s)| !s) {
+        report.changes.push(RowChange::Removed {
+            key: key_object(&key_of(record, &old_keys)),
+            line: *line,
+            record: old.object(record),
+        });
+    }
+    Ok(report)
+}
+
+fn key_indices(side: &Side, keys: &[String], name: &str) -> Result<Vec<usize>> {
+    keys.iter()
+        .map(|k| match side.index(k) {
+            Some(i) => Ok(i),
+            None => bail!("Key column {:?} not found in the {} file", k, name),
+        })
+        .collect()
+}
+
+fn key_text(key: &Map<String, Value>) -> String {
+    key.iter()
+        .map(|(k, v)| match v {
+            Value::String(s) => format!("{}={}", k, s),
+            _ => format!("{}={}", k, v),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn write_text(report: &DiffReport, output: &mut dyn Write) -> Result<()> {
+    for column in &report.columns_added {
+        writeln!(output, "{}", format!("+ column {:?}", column).green())?;
+    }
+    for column in &report.columns_removed {
+        writeln!(output, "{}", format!("- column {:?}", column).red())?;
+    }
+
+    let (mut added, mut removed, mut changed) = (0, 0, 0);
+    for change in &report.changes {
+        match change {
+            RowChange::Added { key, line, .. } => {
+                added += 1;
+                let text = format!("+ {} (line {})", key_text(key), line);
+                writeln!(output, "{}", text.green())?;
+            }
+            RowChange::Removed { key, line, .. } => {
+                removed += 1;
+                let text = format!("- {} (line {})", key_text(key), line);
+                writeln!(output, "{}", text.red())?;
+            }
+            RowChange::Changed {
+                key,
+                old_line,
+                new_line,
+                columns,
+            } => {
+                changed += 1;
+                let text = format!("~ {} (line {} -> {})", key_text(key), old_line, new_line);
+                writeln!(output, "{}", text.yellow())?;
+                for c in columns {
+                    writeln!(
+                        output,
+                        "    {}: {} -> {}",
+                        c.column,
+                        format!("{:?}", c.old).red(),
+                        format!("{:?}", c.new).green()
+                    )?;
+                }
+            }
+        }
+    }
+    writeln!(
+        output,
+        "{} added, {} removed, {} changed",
+        added, removed, changed
+    )?;
+    Ok(())
+}
+
+/// Patch CSV with a `_change` column: added rows carry all their values, changed rows the key
+/// and the new values of the changed columns, removed rows only the key.
+/// `_changed` lists the changed columns separated by `;`, so a value changed to an empty string
+/// is told apart from an unchanged one. Positional diffs add a `_row` column.
+fn write_patch(report: &DiffReport, keys: &[String], output: &mut dyn Write) -> Result<()> {
+    // 只保留有变化的列，按新文件的列顺序排列
+    let used = |column: &String| {
+        report.changes.iter().any(|change| match change {
+            RowChange::Added { record, .. } => record.contains_key(column),
+            RowChange::Removed { .. } => keys.contains(column),
+            RowChange::Changed { columns, .. } => {
+                keys.contains(column) || columns.iter().any(|c| c.column == *column)
+            }
+        })
+    };
+    let headers: Vec<String> = report.headers.iter().filter(|h| used(h)).cloned().collect();
+
+    let mut writer = csv::Writer::from_writer(output);
+    let mut header = vec!["_change".to_string(), "_changed".to_string()];
+    if keys.is_empty() {
+        header.push("_row".to_string());
+    }
+    header.extend(headers.iter().cloned());
+    writer.write_record(&header)?;
+
+    for change in &report.changes {
+        let (name, key, values): (&str, _, Map<String, Value>) = match change {
+            RowChange::Added { key, record, .. } => ("added", key, record.clone()),
+            RowChange::Removed { key, .. } => ("removed", key, key.clone()),
+            RowChange::Changed { key, columns, .. } => {
+                let mut values = key.clone();
+                for c in columns {
+                    values.insert(c.column.clone(), Value::String(c.new.clone()));
+                }
+                ("changed", key, values)
+            }
+        };
+        let changed = match change {
+            RowChange::Changed { columns, .. } => columns
+                .iter()
+                .map(|c| c.column.as_str())
+                .collect::<Vec<_>>()
+                .join(";"),
+            _ => String::new(),
+        };
+        let mut row = vec![name.to_string(), changed];
+        if keys.is_empty() {
+            row.push(key["row"].to_string());
+        }
+        row.extend(headers.iter().map(|h| match values.get(h) {
+            Some(Value::String(s)) => s.clone(),
+            _ => String::new(),
+        }));
+        writer.write_record(&row)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD: &str = "Name,Position,Kit\n\
+                       Buffon,Goalkeeper,77\n\
+                       Perin,Goalkeeper,37\n\
+                       Bonucci,Centre-Back,19\n";
+    const NEW: &str = "Name,Position,Kit,Club\n\
+                       Buffon,Goalkeeper,1,Juventus\n\
+                       Bonucci,Centre-Back,19,Juventus\n\
+                       Kean,Centre-Forward,18,Juventus\n";
+
+    fn reader(content: &'static str) -> Reader<&'static [u8]> {
+        CsvDialect::default()
+            .builder()
+            .from_reader(content.as_bytes())
+    }
+
+    fn key(name: &str) -> Map<String, Value> {
+        Map::from_iter([("Name".to_string(), Value::from(name))])
+    }
+
+    #[test]
+    fn test_keyed_diff() -> Result<()> {
+        let report = diff(reader(OLD), reader(NEW), &["Name".to_string()])?;
+        assert_eq!(report.columns_added, ["Club"]);
+        assert!(report.columns_removed.is_empty());
+        assert_eq!(report.changes.len(), 3);
+        assert_eq!(
+            report.changes[0],
+            RowChange::Changed {
+                key: key("Buffon"),
+                old_line: 2,
+                new_line: 2,
+                columns: vec![ColumnChange {
+                    column: "Kit".to_string(),
+                    old: "77".to_string(),
+                    new: "1".to_string(),
+                }],
+            }
+        );
+        assert!(
+            matches!(&report.changes[1], RowChange::Added { key: k, line: 4, .. } if *k == key("Kean"))
+        );
+        assert!(
+            matches!(&report.changes[2], RowChange::Removed { key: k, line: 3, .. } if *k == key("Perin"))
+        );
+
+        let mut patch = Vec::new();
+        write_patch(&report, &["Name".to_string()], &mut patch)?;
+        assert_eq!(
+            String::from_utf8(patch)?,
+            "_change,_changed,Name,Position,Kit,Club\n\
+             changed,Kit,Buffon,,1,\n\
+             added,,Kean,Centre-Forward,18,Juventus\n\
+             removed,,Perin,,,\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_patch_marks_changed_columns() -> Result<()> {
+        let old = reader("id,a,b\n1,x,y\n2,x,y\n");
+        let new = reader("id,a,b\n1,,y\n2,x,z\n");
+        let report = diff(old, new, &["id".to_string()])?;
+        let mut patch = Vec::new();
+        write_patch(&report, &["id".to_string()], &mut patch)?;
+        // 改为空字符串的列和未改变的列可以区分开
+        assert_eq!(
+            String::from_utf8(patch)?,
+            "_change,_changed,id,a,b\nchanged,a,1,,\nchanged,b,2,,z\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_positional_diff() -> Result<()> {
+        let report = diff(reader(OLD), reader(NEW), &[])?;
+        // 按位置比较时，第2、3行的每一列都不同
+        assert_eq!(report.changes.len(), 3);
+        assert!(
+            matches!(&report.changes[1], RowChange::Changed { columns, .. } if columns.len() == 3)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_duplicate_key() {
+        let old = "Name,Kit\nBuffon,1\nBuffon,77\n";
+        let ret = diff(reader(old), reader(NEW), &["Name".to_string()]);
+        assert!(ret.is_err());
+    }
+}
@@ -0,0 +1,129 @@
+use anyhow::Result;
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use encoding_rs_io::DecodeReaderBytesBuilder;
+use std::io::{Cursor, Read};
+use tracing::info;
+
+/// Bytes inspected for encoding detection
+const SAMPLE_SIZE: u64 = 64 * 1024;
+
+/// Transcode the input to UTF-8, the encoding is detected when not given.
+/// A byte order mark always takes precedence and is stripped.
+pub fn decode_reader(
+    mut reader: Box<dyn Read>,
+    encoding: Option<&'static Encoding>,
+) -> Result<Box<dyn Read>> {
+    let mut sample = Vec::new();
+    reader.by_ref().take(SAMPLE_SIZE).read_to_end(&mut sample)?;
+    let complete = (sample.len() as u64) < SAMPLE_SIZE;
+    let encoding = encoding.unwrap_or_else(|| detect_encoding(&sample, complete));
+    info!("Reading input as {}", encoding.name());
+
+    // 无BOM的UTF-8无需转码
+    let passthrough = encoding == UTF_8 && Encoding::for_bom(&sample).is_none();
+    let reader = Cursor::new(sample).chain(reader);
+    if passthrough {
+        return Ok(Box::new(reader));
+    }
+    Ok(Box::new(
+        DecodeReaderBytesBuilder::new()
+            .encoding(Some(encoding))
+            .build(reader),
+    ))
+}
+
+/// Guess the encoding from a BOM, UTF-16 zero bytes or the byte statistics of the sample
+pub fn detect_encoding(sample: &[u8], complete: bool) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(sample) {
+        return encoding;
+    }
+    if let Some(encoding) = detect_utf16(sample) {
+        return encoding;
+    }
+    match std::str::from_utf8(sample) {
+        Ok(_) => return UTF_8,
+        // 采样可能在多字节字符中间截断
+        Err(e) if e.error_len().is_none() && !complete => return UTF_8,
+        Err(_) => {}
+    }
+
+    let mut detector = EncodingDetector::new();
+    detector.feed(sample, complete);
+    detector.guess(None, true)
+}
+
+/// UTF-16 without BOM: ASCII text has a zero byte in every other position
+fn detect_utf16(sample: &[u8]) -> Option<&'static Encoding> {
+    let pairs = sample.len() / 2;
+    if pairs < 2 {
+        return None;
+    }
+    let zeros = |offset: usize| {
+        sample
+            .iter()
+            .skip(offset)
+            .step_by(2)
+            .filter(|&&b| b == 0)
+            .count()
+    };
+    let (even, odd) = (zeros(0), zeros(1));
+    if odd * 2 > pairs && even == 0 {
+        Some(UTF_16LE)
+    } else if even * 2 > pairs && odd == 0 {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::{GBK, WINDOWS_1252};
+
+    fn decoded(bytes: Vec<u8>) -> Result<String> {
+        let mut content = String::new();
+        decode_reader(Box::new(Cursor::new(bytes)), None)?.read_to_string(&mut content)?;
+        Ok(content)
+    }
+
+    #[test]
+    fn test_decode_utf16_with_bom() -> Result<()> {
+        let mut bytes = vec![0xFF, 0xFE];
+        for unit in "Name,Kit\nBuffon,77\n".encode_utf16() {
+            bytes.extend(unit.to_le_bytes());
+        }
+        assert_eq!(decoded(bytes)?, "Name,Kit\nBuffon,77\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_strip_utf8_bom() -> Result<()> {
+        let bytes = b"\xEF\xBB\xBFName,Kit\n".to_vec();
+        assert_eq!(decoded(bytes)?, "Name,Kit\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_detect_legacy_encodings() {
+        let text = "Name,City\nJosé Müller,Zürich\nFrançois Hôtel,Besançon\n";
+        let (bytes, _, _) = WINDOWS_1252.encode(text);
+        assert_eq!(detect_encoding(&bytes, true), WINDOWS_1252);
+
+        let text = "姓名,城市\n张三,北京\n李四,上海\n王五,广州\n赵六,深圳\n";
+        let (bytes, _, _) = GBK.encode(text);
+        assert_eq!(detect_encoding(&bytes, true), GBK);
+
+        assert_eq!(detect_encoding("姓名,城市\n".as_bytes(), true), UTF_8);
+    }
+
+    #[test]
+    fn test_detect_utf16_without_bom() {
+        let bytes: Vec<u8> = "Name,Kit\n"
+            .encode_utf16()
+            .flat_map(|u| u.to_be_bytes())
+            .collect();
+        assert_eq!(detect_encoding(&bytes, true), UTF_16BE);
+    }
+}
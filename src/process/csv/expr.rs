@@ -0,0 +1,603 @@
+use crate::process::csv::nested::cell;
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveDateTime};
+use serde_json::{Map, Number, Value};
+use std::cmp::Ordering;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// A small expression language over record columns, e.g. `Position == 'Defender' && Kit Number > 10`
+/// or `years_since(DOB) + 1`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(Value),
+    Column(String),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare(CompareOp, Box<Expr>, Box<Expr>),
+    Arith(ArithOp, Box<Expr>, Box<Expr>),
+    Call(Function, Vec<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArithOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+/// Built-in functions, dates are ISO 8601 strings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Function {
+    /// Whole years from the date until today
+    YearsSince,
+    DaysSince,
+    Year,
+    Month,
+    Day,
+    Today,
+    Upper,
+    Lower,
+    Trim,
+    Len,
+    Concat,
+    /// First argument that is neither null nor empty
+    Coalesce,
+    Round,
+    Abs,
+}
+
+impl Function {
+    fn lookup(name: &str) -> Option<Self> {
+        let function = match name.to_lowercase().as_str() {
+            "years_since" => Function::YearsSince,
+            "days_since" => Function::DaysSince,
+            "year" => Function::Year,
+            "month" => Function::Month,
+            "day" => Function::Day,
+            "today" => Function::Today,
+            "upper" => Function::Upper,
+            "lower" => Function::Lower,
+            "trim" => Function::Trim,
+            "len" => Function::Len,
+            "concat" => Function::Concat,
+            "coalesce" => Function::Coalesce,
+            "round" => Function::Round,
+            "abs" => Function::Abs,
+            _ => return None,
+        };
+        Some(function)
+    }
+
+    /// Minimum and maximum number of arguments
+    fn arity(&self) -> (usize, usize) {
+        match self {
+            Function::Today => (0, 0),
+            Function::Concat | Function::Coalesce => (1, usize::MAX),
+            Function::Round => (1, 2),
+            _ => (1, 1),
+        }
+    }
+
+    fn call(&self, args: &[Value]) -> Value {
+        let date = || args.first().and_then(as_date);
+        let text = || args.first().and_then(Value::as_str);
+        let number = || args.first().and_then(as_number);
+        let today = Local::now().date_naive();
+        let value = match self {
+            Function::YearsSince => date().and_then(|d| today.years_since(d)).map(Value::from),
+            Function::DaysSince => date().map(|d| Value::from((today - d).num_days())),
+            Function::Year => date().map(|d| Value::from(d.year())),
+            Function::Month => date().map(|d| Value::from(d.month())),
+            Function::Day => date().map(|d| Value::from(d.day())),
+            Function::Today => Some(Value::String(today.format("%Y-%m-%d").to_string())),
+            Function::Upper => text().map(|s| Value::String(s.to_uppercase())),
+            Function::Lower => text().map(|s| Value::String(s.to_lowercase())),
+            Function::Trim => text().map(|s| Value::String(s.trim().to_string())),
+            Function::Len => text().map(|s| Value::from(s.chars().count())),
+            Function::Concat => Some(Value::String(args.iter().map(cell).collect())),
+            Function::Coalesce => args.iter().find(|v| !cell(v).is_empty()).cloned(),
+            Function::Round => {
+                let digits = args.get(1).and_then(Value::as_i64).unwrap_or(0);
+                let scale = 10f64.powi(digits.clamp(0, 15) as i32);
+                number().map(|n| number_value((n * scale).round() / scale))
+            }
+            Function::Abs => match args.first() {
+                Some(Value::Number(n)) if n.is_i64() => n.as_i64().map(|n| Value::from(n.abs())),
+                _ => number().map(|n| number_value(n.abs())),
+            },
+        };
+        value.unwrap_or(Value::Null)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Column(String),
+    Str(String),
+    Op(CompareOp),
+    Arith(ArithOp),
+    Comma,
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+}
+
+impl Expr {
+    pub fn parse(source: &str) -> Result<Self> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.parse_or()?;
+        if let Some(token) = parser.tokens.get(parser.pos) {
+            bail!("Unexpected {:?} in expression {:?}", token, source);
+        }
+        Ok(expr)
+    }
+
+    /// Names of all columns referenced by the expression
+    pub fn columns(&self) -> Vec<&str> {
+        let mut columns = Vec::new();
+        self.collect_columns(&mut columns);
+        columns
+    }
+
+    fn collect_columns<'a>(&'a self, columns: &mut Vec<&'a str>) {
+        match self {
+            Expr::Literal(_) => {}
+            Expr::Column(name) => columns.push(name),
+            Expr::Not(e) => e.collect_columns(columns),
+            Expr::And(l, r) | Expr::Or(l, r) | Expr::Compare(_, l, r) | Expr::Arith(_, l, r) => {
+                l.collect_columns(columns);
+                r.collect_columns(columns);
+            }
+            Expr::Call(_, args) => args.iter().for_each(|arg| arg.collect_columns(columns)),
+        }
+    }
+
+    pub fn eval(&self, record: &Map<String, Value>) -> Value {
+        match self {
+            Expr::Literal(value) => value.clone(),
+            Expr::Column(name) => record.get(name).cloned().unwrap_or(Value::Null),
+            Expr::Not(e) => Value::Bool(!truthy(&e.eval(record))),
+            Expr::And(l, r) => Value::Bool(truthy(&l.eval(record)) && truthy(&r.eval(record))),
+            Expr::Or(l, r) => Value::Bool(truthy(&l.eval(record)) || truthy(&r.eval(record))),
+            Expr::Compare(op, l, r) => {
+                let ordering = compare(&l.eval(record), &r.eval(record));
+                let matched = match (op, ordering) {
+                    (CompareOp::Eq, ordering) => ordering == Some(Ordering::Equal),
+                    (CompareOp::Ne, ordering) => ordering != Some(Ordering::Equal),
+                    (_, None) => false,
+                    (CompareOp::Lt, Some(o)) => o.is_lt(),
+                    (CompareOp::Le, Some(o)) => o.is_le(),
+                    (CompareOp::Gt, Some(o)) => o.is_gt(),
+                    (CompareOp::Ge, Some(o)) => o.is_ge(),
+                };
+                Value::Bool(matched)
+            }
+            Expr::Arith(op, l, r) => arith(*op, &l.eval(record), &r.eval(record)),
+            Expr::Call(function, args) => {
+                let args: Vec<Value> = args.iter().map(|arg| arg.eval(record)).collect();
+                function.call(&args)
+            }
+        }
+    }
+
+    pub fn matches(&self, record: &Map<String, Value>) -> bool {
+        truthy(&self.eval(record))
+    }
+}
+
+pub fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64() != Some(0.0),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(_) | Value::Object(_) => true,
+    }
+}
+
+/// Compare two values, numbers compare numerically even when one side is a numeric string.
+/// Null is only equal to null and is not ordered against anything else.
+pub fn compare(left: &Value, right: &Value) -> Option<Ordering> {
+    match (left, right) {
+        (Value::Null, Value::Null) => Some(Ordering::Equal),
+        (Value::Null, _) | (_, Value::Null) => None,
+        (Value::Bool(l), Value::Bool(r)) => Some(l.cmp(r)),
+        (Value::String(l), Value::String(r)) => Some(l.cmp(r)),
+        _ => match (as_number(left), as_number(right)) {
+            (Some(l), Some(r)) => l.partial_cmp(&r),
+            _ => Some(left.to_string().cmp(&right.to_string())),
+        },
+    }
+}
+
+/// Integer arithmetic when both sides are integers, otherwise floating point.
+/// Null operands, division by zero and overflow yield null.
+fn arith(op: ArithOp, left: &Value, right: &Value) -> Value {
+    if let (Some(l), Some(r)) = (left.as_i64(), right.as_i64()) {
+        let value = match op {
+            ArithOp::Add => l.checked_add(r),
+            ArithOp::Sub => l.checked_sub(r),
+            ArithOp::Mul => l.checked_mul(r),
+            ArithOp::Rem => l.checked_rem(r),
+            // 整数相除不一定得到整数，按浮点数计算
+            ArithOp::Div => None,
+        };
+        if let Some(value) = value {
+            return Value::from(value);
+        }
+        if op != ArithOp::Div {
+            return Value::Null;
+        }
+    }
+    let (Some(l), Some(r)) = (as_number(left), as_number(right)) else {
+        return Value::Null;
+    };
+    match op {
+        ArithOp::Add => number_value(l + r),
+        ArithOp::Sub => number_value(l - r),
+        ArithOp::Mul => number_value(l * r),
+        ArithOp::Div if r == 0.0 => Value::Null,
+        ArithOp::Div => number_value(l / r),
+        ArithOp::Rem if r == 0.0 => Value::Null,
+        ArithOp::Rem => number_value(l % r),
+    }
+}
+
+/// Integral results are written without a fraction
+fn number_value(n: f64) -> Value {
+    if n.fract() == 0.0 && n.abs() < 1e15 {
+        return Value::from(n as i64);
+    }
+    Number::from_f64(n).map_or(Value::Null, Value::Number)
+}
+
+/// An ISO 8601 date, the date part of a datetime is used
+fn as_date(value: &Value) -> Option<NaiveDate> {
+    let value = value.as_str()?.trim();
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Some(date);
+    }
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(value) {
+        return Some(datetime.date_naive());
+    }
+    ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .map(|datetime| datetime.date())
+}
+
+fn as_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' => {
+                chars.next();
+                tokens.push(if c == '(' {
+                    Token::LParen
+                } else {
+                    Token::RParen
+                });
+            }
+            '\'' | '"' => {
+                chars.next();
+                tokens.push(Token::Str(quoted(&mut chars, c)?));
+            }
+            ',' => {
+                chars.next();
+                tokens.push(Token::Comma);
+            }
+            '+' | '*' | '/' | '%' => {
+                chars.next();
+                tokens.push(Token::Arith(match c {
+                    '+' => ArithOp::Add,
+                    '*' => ArithOp::Mul,
+                    '/' => ArithOp::Div,
+                    _ => ArithOp::Rem,
+                }));
+            }
+            // 单独的 '-' 是减号，紧跟字符时属于列名或负数，例如 a-b、-5
+            '-' if is_minus(&chars) => {
+                chars.next();
+                tokens.push(Token::Arith(ArithOp::Sub));
+            }
+            '`' => {
+                chars.next();
+                tokens.push(Token::Column(quoted(&mut chars, c)?));
+            }
+            '&' | '|' => {
+                chars.next();
+                if chars.next() != Some(c) {
+                    bail!("Expected {}{} in expression {:?}", c, c, source);
+                }
+                tokens.push(if c == '&' { Token::And } else { Token::Or });
+            }
+            '=' | '!' | '<' | '>' => {
+                chars.next();
+                let eq = chars.next_if_eq(&'=').is_some();
+                let token = match (c, eq) {
+                    ('=', _) => Token::Op(CompareOp::Eq),
+                    ('!', true) => Token::Op(CompareOp::Ne),
+                    ('!', false) => Token::Not,
+                    ('<', true) => Token::Op(CompareOp::Le),
+                    ('<', false) => Token::Op(CompareOp::Lt),
+                    ('>', true) => Token::Op(CompareOp::Ge),
+                    _ => Token::Op(CompareOp::Gt),
+                };
+                tokens.push(token);
+            }
+            c if is_word_char(c) || c == '-' => {
+                let mut word = String::new();
+                while let Some(c) = chars.next_if(|&c| is_word_char(c) || c == '-') {
+                    word.push(c);
+                }
+                tokens.push(Token::Word(word));
+            }
+            _ => bail!("Unexpected character {:?} in expression {:?}", c, source),
+        }
+    }
+    Ok(tokens)
+}
+
+fn is_minus(chars: &Peekable<Chars>) -> bool {
+    let mut rest = chars.clone();
+    rest.next();
+    rest.peek().is_none_or(|c| c.is_whitespace() || *c == '(')
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '.'
+}
+
+fn quoted(chars: &mut Peekable<Chars>, quote: char) -> Result<String> {
+    let mut value = String::new();
+    loop {
+        match chars.next() {
+            Some('\\') => value.extend(chars.next()),
+            Some(c) if c == quote => return Ok(value),
+            Some(c) => value.push(c),
+            None => bail!("Unterminated {} in expression", quote),
+        }
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next_if(&mut self, token: &Token) -> bool {
+        if self.tokens.get(self.pos) == Some(token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_and()?;
+        while self.next_if(&Token::Or) {
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_not()?;
+        while self.next_if(&Token::And) {
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_not()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_not(&mut self) -> Result<Expr> {
+        if self.next_if(&Token::Not) {
+            return Ok(Expr::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_compare()
+    }
+
+    fn parse_compare(&mut self) -> Result<Expr> {
+        let left = self.parse_additive()?;
+        if let Some(Token::Op(op)) = self.tokens.get(self.pos).cloned() {
+            self.pos += 1;
+            let right = self.parse_additive()?;
+            return Ok(Expr::Compare(op, Box::new(left), Box::new(right)));
+        }
+        Ok(left)
+    }
+
+    fn parse_additive(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_multiplicative()?;
+        while let Some(Token::Arith(op @ (ArithOp::Add | ArithOp::Sub))) =
+            self.tokens.get(self.pos).cloned()
+        {
+            self.pos += 1;
+            expr = Expr::Arith(op, Box::new(expr), Box::new(self.parse_multiplicative()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_multiplicative(&mut self) -> Result<Expr> {
+        let mut expr = self.parse_primary()?;
+        while let Some(Token::Arith(op @ (ArithOp::Mul | ArithOp::Div | ArithOp::Rem))) =
+            self.tokens.get(self.pos).cloned()
+        {
+            self.pos += 1;
+            expr = Expr::Arith(op, Box::new(expr), Box::new(self.parse_primary()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_call(&mut self, name: &str) -> Result<Expr> {
+        let function =
+            Function::lookup(name).ok_or_else(|| anyhow!("Unknown function {:?}", name))?;
+        let mut args = Vec::new();
+        if !self.next_if(&Token::RParen) {
+            loop {
+                args.push(self.parse_or()?);
+                if self.next_if(&Token::RParen) {
+                    break;
+                }
+                if !self.next_if(&Token::Comma) {
+                    bail!("Expected ',' or ')' in arguments of {}", name);
+                }
+            }
+        }
+        let (min, max) = function.arity();
+        if args.len() < min || args.len() > max {
+            bail!("Wrong number of arguments for {}", name);
+        }
+        Ok(Expr::Call(function, args))
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| anyhow!("Unexpected end of expression"))?;
+        self.pos += 1;
+        match token {
+            Token::Str(s) => Ok(Expr::Literal(Value::String(s))),
+            Token::Column(name) => Ok(Expr::Column(name)),
+            Token::LParen => {
+                let expr = self.parse_or()?;
+                if !self.next_if(&Token::RParen) {
+                    bail!("Missing closing parenthesis");
+                }
+                Ok(expr)
+            }
+            Token::Word(word) => {
+                // 相邻的单词组成带空格的列名，例如 Kit Number
+                let mut words = vec![word];
+                while let Some(Token::Word(word)) = self.tokens.get(self.pos) {
+                    words.push(word.clone());
+                    self.pos += 1;
+                }
+                if words.len() > 1 {
+                    return Ok(Expr::Column(words.join(" ")));
+                }
+                let word = words.remove(0);
+                if self.next_if(&Token::LParen) {
+                    return self.parse_call(&word);
+                }
+                Ok(match word.as_str() {
+                    "true" => Expr::Literal(Value::Bool(true)),
+                    "false" => Expr::Literal(Value::Bool(false)),
+                    "null" => Expr::Literal(Value::Null),
+                    _ => match literal_number(&word) {
+                        Some(number) => Expr::Literal(number),
+                        None => Expr::Column(word),
+                    },
+                })
+            }
+            token => bail!("Unexpected {:?} in expression", token),
+        }
+    }
+}
+
+fn literal_number(word: &str) -> Option<Value> {
+    if let Ok(n) = word.parse::<i64>() {
+        return Some(Value::from(n));
+    }
+    word.parse::<f64>()
+        .ok()
+        .filter(|n| n.is_finite())
+        .map(Value::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn record() -> Map<String, Value> {
+        match json!({"Name": "Buffon", "Position": "Goalkeeper", "Kit Number": 77, "DOB": null}) {
+            Value::Object(map) => map,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_parse_column_with_spaces() -> Result<()> {
+        let expr = Expr::parse("Position == 'Goalkeeper' && Kit Number > 10")?;
+        assert_eq!(expr.columns(), ["Position", "Kit Number"]);
+        assert!(expr.matches(&record()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_eval() -> Result<()> {
+        let record = record();
+        assert!(Expr::parse("`Kit Number` >= 77")?.matches(&record));
+        assert!(!Expr::parse("Kit Number < '8'")?.matches(&record));
+        assert!(Expr::parse("!(Name == 'Perin') || DOB > 1")?.matches(&record));
+        assert!(Expr::parse("DOB == null")?.matches(&record));
+        assert!(!Expr::parse("DOB > 1")?.matches(&record));
+        assert!(Expr::parse("Name ==").is_err());
+        assert!(Expr::parse("(Name == 'Buffon'").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_arithmetic() -> Result<()> {
+        let record = record();
+        let eval = |source: &str| Expr::parse(source).map(|e| e.eval(&record));
+        assert_eq!(eval("Kit Number + 1 * 2")?, json!(79));
+        assert_eq!(eval("(Kit Number - 7) / 4")?, json!(17.5));
+        assert_eq!(eval("Kit Number % 10 == 7")?, json!(true));
+        assert_eq!(eval("Kit Number / 0")?, Value::Null);
+        assert_eq!(eval("DOB + 1")?, Value::Null);
+        assert_eq!(eval("-5 * 2")?, json!(-10));
+        Ok(())
+    }
+
+    #[test]
+    fn test_functions() -> Result<()> {
+        let mut record = record();
+        let birthday = Local::now().date_naive() - chrono::Months::new(12 * 41);
+        record.insert("DOB".into(), json!(birthday.format("%Y-%m-%d").to_string()));
+        let eval = |source: &str| Expr::parse(source).map(|e| e.eval(&record));
+        assert_eq!(eval("years_since(DOB)")?, json!(41));
+        assert_eq!(eval("upper(Name)")?, json!("BUFFON"));
+        assert_eq!(eval("concat(Name, ' #', Kit Number)")?, json!("Buffon #77"));
+        assert_eq!(eval("coalesce(Club, '', Position)")?, json!("Goalkeeper"));
+        assert_eq!(eval("round(Kit Number / 3, 2)")?, json!(25.67));
+        assert_eq!(eval("len(Name) > 5")?, json!(true));
+        assert!(Expr::parse("unknown(Name)").is_err());
+        assert!(Expr::parse("upper(Name, Position)").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,221 @@
+use crate::process::csv::expr::{compare, Expr};
+use anyhow::{bail, Result};
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Default)]
+pub struct FilterOptions {
+    pub select: Vec<String>,
+    pub filter: Option<String>,
+    pub sort_by: Vec<String>,
+    pub unique: Vec<String>,
+}
+
+struct SortKey {
+    column: String,
+    descending: bool,
+}
+
+/// Applies `--where`, `--unique`, `--sort-by` and `--select` to a stream of records, in that order
+pub struct RecordFilter {
+    select: Vec<String>,
+    filter: Option<Expr>,
+    sort_by: Vec<SortKey>,
+    unique: Vec<String>,
+}
+
+impl RecordFilter {
+    pub fn new(headers: &[String], opts: &FilterOptions) -> Result<Self> {
+        let filter = opts.filter.as_deref().map(Expr::parse).transpose()?;
+        let sort_by = opts
+            .sort_by
+            .iter()
+            .map(|key| match key.rsplit_once(':') {
+                Some((column, order)) if order.eq_ignore_ascii_case("desc") => SortKey {
+                    column: column.to_string(),
+                    descending: true,
+                },
+                Some((column, order)) if order.eq_ignore_ascii_case("asc") => SortKey {
+                    column: column.to_string(),
+                    descending: false,
+                },
+                _ => SortKey {
+                    column: key.to_string(),
+                    descending: false,
+                },
+            })
+            .collect::<Vec<_>>();
+
+        let referenced = opts
+            .select
+            .iter()
+            .chain(opts.unique.iter())
+            .map(String::as_str)
+            .chain(sort_by.iter().map(|key| key.column.as_str()))
+            .chain(filter.iter().flat_map(|f| f.columns()));
+        for column in referenced {
+            if !headers.iter().any(|h| h == column) {
+                bail!("Column {:?} not found", column);
+            }
+        }
+
+        Ok(Self {
+            select: opts.select.clone(),
+            filter,
+            sort_by,
+            unique: opts.unique.clone(),
+        })
+    }
+
+    /// Without `--sort-by` and `--unique` every record is filtered on its own
+    pub fn is_streaming(&self) -> bool {
+        self.sort_by.is_empty() && self.unique.is_empty()
+    }
+
+    /// `--where` and `--select` of a single record, `None` when it is filtered out.
+    /// Only for streaming filters, see `is_streaming`.
+    pub fn apply_record(&self, record: Value) -> Option<Value> {
+        if let (Some(filter), Value::Object(object)) = (&self.filter, &record) {
+            if !filter.matches(object) {
+                return None;
+            }
+        }
+        Some(select_columns(&self.select, record))
+    }
+
+    /// Wrap the records, sorting is the only stage that needs to buffer them
+    pub fn apply<'a>(
+        self,
+        records: impl Iterator<Item = Result<Value>> + 'a,
+    ) -> Result<Box<dyn Iterator<Item = Result<Value>> + 'a>> {
+        let Self {
+            select,
+            filter,
+            sort_by,
+            unique,
+        } = self;
+
+        let mut seen = HashSet::new();
+        let records = records.filter(move |result| {
+            let Ok(Value::Object(record)) = result else {
+                return true;
+            };
+            if filter.as_ref().is_some_and(|f| !f.matches(record)) {
+                return false;
+            }
+            if unique.is_empty() {
+                return true;
+            }
+            let key = unique
+                .iter()
+                .map(|column| record.get(column).cloned().unwrap_or(Value::Null))
+                .collect::<Vec<_>>();
+            seen.insert(Value::Array(key).to_string())
+        });
+
+        let records: Box<dyn Iterator<Item = Result<Value>> + 'a> = if sort_by.is_empty() {
+            Box::new(records)
+        } else {
+            let mut records = records.collect::<Result<Vec<_>>>()?;
+            // 稳定排序，排序键相同的记录保持原有顺序
+            records.sort_by(|a, b| {
+                sort_by
+                    .iter()
+                    .map(|key| {
+                        let ordering = order(&a[&key.column], &b[&key.column]);
+                        if key.descending {
+                            ordering.reverse()
+                        } else {
+                            ordering
+                        }
+                    })
+                    .find(|o| o.is_ne())
+                    .unwrap_or(Ordering::Equal)
+            });
+            Box::new(records.into_iter().map(Ok))
+        };
+
+        if select.is_empty() {
+            return Ok(records);
+        }
+        Ok(Box::new(
+            records.map(move |result| Ok(select_columns(&select, result?))),
+        ))
+    }
+}
+
+fn select_columns(select: &[String], record: Value) -> Value {
+    if select.is_empty() {
+        return record;
+    }
+    let selected = select
+        .iter()
+        .map(|column| (column.clone(), record[column].clone()))
+        .collect::<Map<_, _>>();
+    Value::Object(selected)
+}
+
+/// Total order for sorting, null values sort last
+fn order(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Null, Value::Null) => Ordering::Equal,
+        (Value::Null, _) => Ordering::Greater,
+        (_, Value::Null) => Ordering::Less,
+        _ => compare(a, b).unwrap_or(Ordering::Equal),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_filter_records() -> Result<()> {
+        let headers = vec![
+            "Name".to_string(),
+            "Position".to_string(),
+            "Kit".to_string(),
+        ];
+        let records = vec![
+            json!({"Name": "Bonucci", "Position": "Defender", "Kit": 19}),
+            json!({"Name": "Buffon", "Position": "Goalkeeper", "Kit": 77}),
+            json!({"Name": "Chiellini", "Position": "Defender", "Kit": 3}),
+            json!({"Name": "Alex Sandro", "Position": "Defender", "Kit": 12}),
+        ];
+        let opts = FilterOptions {
+            select: vec!["Name".into()],
+            filter: Some("Position == 'Defender' && Kit > 10".into()),
+            sort_by: vec!["Kit:desc".into()],
+            unique: vec![],
+        };
+        let ret = RecordFilter::new(&headers, &opts)?
+            .apply(records.into_iter().map(Ok))?
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(
+            ret,
+            [json!({"Name": "Bonucci"}), json!({"Name": "Alex Sandro"})]
+        );
+
+        let opts = FilterOptions {
+            unique: vec!["Position".into()],
+            ..Default::default()
+        };
+        let records = vec![
+            json!({"Position": "Defender"}),
+            json!({"Position": "Defender"}),
+        ];
+        let ret = RecordFilter::new(&headers, &opts)?
+            .apply(records.into_iter().map(Ok))?
+            .count();
+        assert_eq!(ret, 1);
+
+        let opts = FilterOptions {
+            sort_by: vec!["Missing".into()],
+            ..Default::default()
+        };
+        assert!(RecordFilter::new(&headers, &opts).is_err());
+        Ok(())
+    }
+}
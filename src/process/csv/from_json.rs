@@ -0,0 +1,74 @@
+use crate::cli::csv::InputFormat;
+use crate::get_reader;
+use crate::process::csv::nested::{cell, flatten, FlattenOptions};
+use anyhow::Result;
+use serde_json::{Deserializer, Value};
+use std::collections::HashSet;
+use std::io::Read;
+
+pub fn process_json_to_csv(
+    input: &str,
+    output: String,
+    format: Option<InputFormat>,
+    delimiter: u8,
+    opts: &FlattenOptions,
+) -> Result<()> {
+    let mut content = String::new();
+    get_reader(input)?.read_to_string(&mut content)?;
+    let records = parse_records(&content, format.unwrap_or_else(|| detect_format(&content)))?;
+
+    // 表头取所有记录键的并集，按首次出现的顺序排列
+    let mut headers: Vec<String> = Vec::new();
+    let mut seen = HashSet::new();
+    let mut rows = Vec::with_capacity(records.len());
+    for record in &records {
+        for row in flatten(record, opts) {
+            for key in row.keys() {
+                if seen.insert(key.clone()) {
+                    headers.push(key.clone());
+                }
+            }
+            rows.push(row);
+        }
+    }
+
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_path(output)?;
+    writer.write_record(&headers)?;
+    for row in rows {
+        writer.write_record(
+            headers
+                .iter()
+                .map(|h| row.get(h).map(cell).unwrap_or_default()),
+        )?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+fn detect_format(content: &str) -> InputFormat {
+    match content.trim_start().chars().next() {
+        Some('[') => InputFormat::Json,
+        Some('{') => InputFormat::Ndjson,
+        _ => InputFormat::Yaml,
+    }
+}
+
+fn parse_records(content: &str, format: InputFormat) -> Result<Vec<Value>> {
+    let value = match format {
+        InputFormat::Json => serde_json::from_str(content)?,
+        InputFormat::Yaml => serde_yaml::from_str(content)?,
+        InputFormat::Ndjson => {
+            return Ok(Deserializer::from_str(content)
+                .into_iter::<Value>()
+                .collect::<Result<_, _>>()?);
+        }
+    };
+
+    match value {
+        Value::Array(records) => Ok(records),
+        value => Ok(vec![value]),
+    }
+}
@@ -0,0 +1,349 @@
+use crate::cli::csv::{ColumnTypes, JoinMode, OutputFormat};
+use crate::process::csv::dialect::CsvDialect;
+use crate::process::csv::nested::cell;
+use crate::process::csv::reader::TypedReader;
+use crate::process::csv::writer::{record_writer, RecordWriter, WriterOptions};
+use anyhow::{anyhow, bail, Result};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+
+pub struct JoinOptions {
+    pub mode: JoinMode,
+    /// Key columns, `name` or `left=right` when the names differ
+    pub on: Vec<String>,
+    pub format: OutputFormat,
+    pub dialect: CsvDialect,
+    pub types: ColumnTypes,
+    /// Prefixes for non-key columns present on both sides
+    pub left_prefix: String,
+    pub right_prefix: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Left,
+    Right,
+}
+
+/// Where an output column takes its value from
+enum Source {
+    /// Key column, taken from whichever side has the record
+    Key(usize),
+    Left(String),
+    Right(String),
+}
+
+/// Join two CSV files, the smaller file is kept in memory and the larger one is streamed.
+/// Records come out in the order of the streamed file, unmatched records of the hashed file last.
+pub fn process_join(
+    left: &str,
+    right: &str,
+    output: Box<dyn Write>,
+    opts: &JoinOptions,
+) -> Result<()> {
+    if left == "-" && right == "-" {
+        bail!("Only one side of the join can be read from stdin");
+    }
+    let left_reader = TypedReader::open(left, &opts.dialect, true, &opts.types)?;
+    let right_reader = TypedReader::open(right, &opts.dialect, true, &opts.types)?;
+    let join = Join::new(left_reader.headers(), right_reader.headers(), opts)?;
+
+    let mut writer = record_writer(opts.format, output, &WriterOptions::default());
+    if stream_left(left, right) {
+        let table = HashTable::load(right_reader.records(), &join.right_keys)?;
+        join.run(Side::Left, left_reader.records(), table, writer.as_mut())?;
+    } else {
+        let table = HashTable::load(left_reader.records(), &join.left_keys)?;
+        join.run(Side::Right, right_reader.records(), table, writer.as_mut())?;
+    }
+    writer.finish()
+}
+
+/// stdin can only be streamed, otherwise the larger file is streamed
+fn stream_left(left: &str, right: &str) -> bool {
+    let size = |input: &str| {
+        (input != "-")
+            .then(|| fs::metadata(input).map(|m| m.len()).ok())
+            .flatten()
+    };
+    match (size(left), size(right)) {
+        (None, _) => true,
+        (_, None) => false,
+        (Some(l), Some(r)) => l >= r,
+    }
+}
+
+struct Join {
+    mode: JoinMode,
+    left_keys: Vec<String>,
+    right_keys: Vec<String>,
+    columns: Vec<(String, Source)>,
+}
+
+impl Join {
+    fn new(left: &[String], right: &[String], opts: &JoinOptions) -> Result<Self> {
+        if opts.on.is_empty() {
+            bail!("At least one key column is required");
+        }
+        let mut left_keys = Vec::with_capacity(opts.on.len());
+        let mut right_keys = Vec::with_capacity(opts.on.len());
+        for key in &opts.on {
+            let (l, r) = key.split_once('=').unwrap_or((key, key));
+            let (l, r) = (l.trim(), r.trim());
+            for (name, headers, side) in [(l, left, "left"), (r, right, "right")] {
+                if !headers.iter().any(|h| h == name) {
+                    bail!("Key column {:?} not found in the {} file", name, side);
+                }
+            }
+            left_keys.push(l.to_string());
+            right_keys.push(r.to_string());
+        }
+
+        // 两边都有的非键列加上前缀，避免覆盖
+        let right_values: Vec<&String> = right.iter().filter(|h| !right_keys.contains(h)).collect();
+        let mut columns = Vec::with_capacity(left.len() + right_values.len());
+        for header in left {
+            let name = match left_keys.iter().position(|k| k == header) {
+                Some(i) => (header.clone(), Source::Key(i)),
+                None if right_values.contains(&header) => (
+                    format!("{}{}", opts.left_prefix, header),
+                    Source::Left(header.clone()),
+                ),
+                None => (header.clone(), Source::Left(header.clone())),
+            };
+            columns.push(name);
+        }
+        for header in right_values {
+            let name = if left.contains(header) {
+                format!("{}{}", opts.right_prefix, header)
+            } else {
+                header.clone()
+            };
+            columns.push((name, Source::Right(header.clone())));
+        }
+        for (i, (name, _)) in columns.iter().enumerate() {
+            if columns[..i].iter().any(|(n, _)| n == name) {
+                bail!("Duplicate output column {:?}, use different prefixes", name);
+            }
+        }
+
+        Ok(Self {
+            mode: opts.mode,
+            left_keys,
+            right_keys,
+            columns,
+        })
+    }
+
+    fn keeps_unmatched(&self, side: Side) -> bool {
+        matches!(
+            (self.mode, side),
+            (JoinMode::Full, _) | (JoinMode::Left, Side::Left) | (JoinMode::Right, Side::Right)
+        )
+    }
+
+    fn run(
+        &self,
+        streamed: Side,
+        records: impl Iterator<Item = Result<Value>>,
+        mut table: HashTable,
+        writer: &mut dyn RecordWriter,
+    ) -> Result<()> {
+        let hashed = match streamed {
+            Side::Left => Side::Right,
+            Side::Right => Side::Left,
+        };
+        let keys = match streamed {
+            Side::Left => &self.left_keys,
+            Side::Right => &self.right_keys,
+        };
+
+        for record in records {
+            let record = into_object(record?)?;
+            let matches = key_of(&record, keys).and_then(|key| table.index.get(&key));
+            match matches {
+                Some(rows) => {
+                    for &i in rows {
+                        table.matched[i] = true;
+                        writer.write(&self.combine(
+                            streamed,
+                            Some(&record),
+                            Some(&table.rows[i]),
+                        ))?;
+                    }
+                }
+                None if self.keeps_unmatched(streamed) => {
+                    writer.write(&self.combine(streamed, Some(&record), None))?;
+                }
+                None => {}
+            }
+        }
+
+        if self.keeps_unmatched(hashed) {
+            for (row, _) in table.rows.iter().zip(&table.matched).filter(|(_, &m)| !m) {
+                writer.write(&self.combine(hashed, Some(row), None))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Build an output record from a record of `side` and its match on the other side
+    fn combine(
+        &self,
+        side: Side,
+        record: Option<&Map<String, Value>>,
+        other: Option<&Map<String, Value>>,
+    ) -> Value {
+        let (left, right) = match side {
+            Side::Left => (record, other),
+            Side::Right => (other, record),
+        };
+        let get = |record: Option<&Map<String, Value>>, name: &str| {
+            record
+                .and_then(|r| r.get(name))
+                .cloned()
+                .unwrap_or_default()
+        };
+        let object = self
+            .columns
+            .iter()
+            .map(|(name, source)| {
+                let value = match source {
+                    Source::Key(i) => match get(left, &self.left_keys[*i]) {
+                        Value::Null => get(right, &self.right_keys[*i]),
+                        value => value,
+                    },
+                    Source::Left(column) => get(left, column),
+                    Source::Right(column) => get(right, column),
+                };
+                (name.clone(), value)
+            })
+            .collect();
+        Value::Object(object)
+    }
+}
+
+/// Records of the smaller side indexed by their key
+struct HashTable {
+    rows: Vec<Map<String, Value>>,
+    matched: Vec<bool>,
+    index: HashMap<Vec<String>, Vec<usize>>,
+}
+
+impl HashTable {
+    fn load(records: impl Iterator<Item = Result<Value>>, keys: &[String]) -> Result<Self> {
+        let mut rows = Vec::new();
+        let mut index: HashMap<Vec<String>, Vec<usize>> = HashMap::new();
+        for record in records {
+            let record = into_object(record?)?;
+            if let Some(key) = key_of(&record, keys) {
+                index.entry(key).or_default().push(rows.len());
+            }
+            rows.push(record);
+        }
+        Ok(Self {
+            matched: vec![false; rows.len()],
+            rows,
+            index,
+        })
+    }
+}
+
+/// Text of the key columns, records with an empty key never match
+fn key_of(record: &Map<String, Value>, keys: &[String]) -> Option<Vec<String>> {
+    keys.iter()
+        .map(|k| {
+            let value = cell(record.get(k)?);
+            (!value.is_empty()).then_some(value)
+        })
+        .collect()
+}
+
+fn into_object(record: Value) -> Result<Map<String, Value>> {
+    match record {
+        Value::Object(object) => Ok(object),
+        _ => Err(anyhow!("Expected a record, got {}", record)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::csv::writer::tests::SharedBuf;
+
+    fn join(mode: JoinMode) -> Result<Vec<Value>> {
+        let buf = SharedBuf::default();
+        let opts = JoinOptions {
+            mode,
+            on: vec!["Name=Player".to_string()],
+            format: OutputFormat::Json,
+            dialect: CsvDialect::default(),
+            types: ColumnTypes::default(),
+            left_prefix: "left_".to_string(),
+            right_prefix: "right_".to_string(),
+        };
+        process_join(
+            "assets/juventus.csv",
+            "assets/juventus_stats.csv",
+            Box::new(buf.clone()),
+            &opts,
+        )?;
+        let records: Vec<Value> = serde_json::from_slice(&buf.0.borrow())?;
+        Ok(records)
+    }
+
+    #[test]
+    fn test_join_modes() -> Result<()> {
+        assert_eq!(join(JoinMode::Inner)?.len(), 5);
+        assert_eq!(join(JoinMode::Left)?.len(), 27);
+        assert_eq!(join(JoinMode::Right)?.len(), 6);
+        assert_eq!(join(JoinMode::Full)?.len(), 28);
+        Ok(())
+    }
+
+    #[test]
+    fn test_join_columns() -> Result<()> {
+        let records = join(JoinMode::Full)?;
+        let keys: Vec<_> = records[0].as_object().unwrap().keys().collect();
+        assert_eq!(
+            keys,
+            [
+                "Name",
+                "Position",
+                "DOB",
+                "Nationality",
+                "left_Kit Number",
+                "right_Kit Number",
+                "Appearances",
+                "Goals"
+            ]
+        );
+
+        let ronaldo = records.iter().find(|r| r["Name"] == "Cristiano Ronaldo");
+        assert_eq!(ronaldo.unwrap()["Goals"], 31);
+        // 只在右边出现的记录，键取右边的值
+        let kean = records.last().unwrap();
+        assert_eq!(kean["Name"], "Moise Kean");
+        assert_eq!(kean["Position"], Value::Null);
+        assert_eq!(kean["right_Kit Number"], 18);
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_key_column() {
+        let opts = JoinOptions {
+            mode: JoinMode::Inner,
+            on: vec!["Name".to_string()],
+            format: OutputFormat::Json,
+            dialect: CsvDialect::default(),
+            types: ColumnTypes::default(),
+            left_prefix: "left_".to_string(),
+            right_prefix: "right_".to_string(),
+        };
+        let left = vec!["Name".to_string()];
+        let right = vec!["Player".to_string()];
+        assert!(Join::new(&left, &right, &opts).is_err());
+    }
+}
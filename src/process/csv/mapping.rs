@@ -0,0 +1,181 @@
+use crate::cli::csv::{ColumnType, ColumnTypes};
+use crate::process::csv::nested::insert_path;
+use crate::process::csv::schema::load_config;
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// How CSV columns are renamed, typed and nested in the converted records
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Mapping {
+    #[serde(default)]
+    pub columns: Vec<ColumnMapping>,
+    /// Columns left out of the output
+    #[serde(default)]
+    pub drop: Vec<String>,
+    /// Keep the columns that are not mentioned in the mapping
+    #[serde(default = "default_keep_unmapped")]
+    pub keep_unmapped: bool,
+    /// Separator of nested field names, e.g. `birth.date`
+    #[serde(default = "default_separator")]
+    pub separator: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ColumnMapping {
+    /// Column name in the CSV header
+    pub column: String,
+    /// Field name in the output, defaults to the column name
+    pub name: Option<String>,
+    #[serde(rename = "type")]
+    pub ty: Option<ColumnType>,
+    /// Used when the cell is empty or the column does not exist
+    pub default: Option<Value>,
+}
+
+fn default_keep_unmapped() -> bool {
+    true
+}
+
+fn default_separator() -> String {
+    ".".to_string()
+}
+
+impl Mapping {
+    pub fn load(path: &str) -> Result<Self> {
+        load_config(path)
+    }
+
+    /// Column types declared by the mapping, explicit overrides take precedence
+    pub fn types(&self, overrides: &ColumnTypes) -> ColumnTypes {
+        let mut types = overrides.clone();
+        for column in &self.columns {
+            if let Some(ty) = &column.ty {
+                types.0.push((column.column.clone(), ty.clone()));
+            }
+        }
+        types
+    }
+}
+
+/// Applies a mapping to typed records
+pub struct RecordMapper {
+    mapping: Mapping,
+    /// Mapped columns missing from the input, they always take their default value
+    missing: Vec<usize>,
+}
+
+impl RecordMapper {
+    pub fn new(headers: &[String], mapping: Mapping) -> Result<Self> {
+        for name in &mapping.drop {
+            if !headers.contains(name) {
+                bail!("Column {:?} to drop not found", name);
+            }
+        }
+        let mut missing = Vec::new();
+        for (i, column) in mapping.columns.iter().enumerate() {
+            if !headers.contains(&column.column) {
+                if column.default.is_none() {
+                    bail!("Column {:?} not found", column.column);
+                }
+                missing.push(i);
+            }
+        }
+        Ok(Self { mapping, missing })
+    }
+
+    pub fn apply(&self, record: Value) -> Result<Value> {
+        let Value::Object(record) = record else {
+            return Ok(record);
+        };
+
+        let mut object = Map::with_capacity(record.len());
+        for (key, value) in record {
+            if self.mapping.drop.contains(&key) {
+                continue;
+            }
+            match self.mapping.columns.iter().find(|c| c.column == key) {
+                Some(column) => {
+                    let value = match (&value, &column.default) {
+                        (Value::Null, Some(default)) => default.clone(),
+                        (Value::String(s), Some(default)) if s.is_empty() => default.clone(),
+                        _ => value,
+                    };
+                    let name = column.name.as_ref().unwrap_or(&column.column);
+                    insert_path(&mut object, name, &self.mapping.separator, value)?;
+                }
+                None if self.mapping.keep_unmapped => {
+                    object.insert(key, value);
+                }
+                None => {}
+            }
+        }
+        for &i in &self.missing {
+            let column = &self.mapping.columns[i];
+            let name = column.name.as_ref().unwrap_or(&column.column);
+            let value = column.default.clone().unwrap_or_default();
+            insert_path(&mut object, name, &self.mapping.separator, value)?;
+        }
+        Ok(Value::Object(object))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::csv::dialect::CsvDialect;
+    use crate::process::csv::reader::TypedReader;
+    use serde_json::json;
+
+    #[test]
+    fn test_map_juventus() -> Result<()> {
+        let mapping: Mapping = serde_yaml::from_str(
+            r#"
+columns:
+  - column: Name
+    name: name
+  - column: DOB
+    name: birth.date
+    type: "date:%b %d, %Y"
+  - column: Nationality
+    name: birth.country
+  - column: Kit Number
+    name: kit
+    type: int
+  - column: Club
+    name: club
+    default: Juventus
+drop: [Position]
+"#,
+        )?;
+        let reader = TypedReader::open(
+            "assets/juventus.csv",
+            &CsvDialect::default(),
+            true,
+            &mapping.types(&ColumnTypes::default()),
+        )?;
+        let mapper = RecordMapper::new(reader.headers(), mapping)?;
+        let record = mapper.apply(reader.records().next().unwrap()?)?;
+        assert_eq!(
+            record,
+            json!({
+                "name": "Wojciech Szczesny",
+                "birth": {"date": "1990-04-18", "country": "Poland"},
+                "kit": 1,
+                "club": "Juventus",
+            })
+        );
+        let keys: Vec<_> = record.as_object().unwrap().keys().collect();
+        assert_eq!(keys, ["name", "birth", "kit", "club"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_column() {
+        let mapping: Mapping = serde_yaml::from_str("columns:\n  - column: Salary\n").unwrap();
+        let headers = vec!["Name".to_string()];
+        assert!(RecordMapper::new(&headers, mapping).is_err());
+    }
+}
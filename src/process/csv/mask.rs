@@ -0,0 +1,291 @@
+use crate::process::crypto::blake3::Blake3;
+use crate::process::csv::dialect::{csv_headers, sniffed_csv_reader, CsvDialect};
+use crate::process::text::KeyLoader;
+use anyhow::{anyhow, bail, Context, Result};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use regex::Regex;
+use std::io::Write;
+
+pub struct MaskOptions {
+    /// `column=strategy` rules
+    pub columns: Vec<String>,
+    /// Key file for keyed hashing, required by `hash` and `fake`
+    pub key: Option<String>,
+    pub dialect: CsvDialect,
+}
+
+/// How the values of a column are masked, empty values always stay empty
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Strategy {
+    /// Hex digest of the keyed hash, truncated to N characters
+    Hash(usize),
+    /// Random characters of the same class (letter case, digit) seeded by the keyed hash
+    Fake,
+    Redact(String),
+    /// Only the first four-digit year of a date is kept
+    YearOnly,
+    /// Keep the last N characters
+    Partial(usize),
+    Null,
+}
+
+impl Strategy {
+    fn parse(value: &str) -> Result<Self> {
+        let (name, arg) = match value.trim().split_once(':') {
+            Some((name, arg)) => (name, Some(arg)),
+            None => (value.trim(), None),
+        };
+        let number = |default: usize| -> Result<usize> {
+            arg.map_or(Ok(default), |n| {
+                n.parse()
+                    .with_context(|| format!("Invalid number in {:?}", value))
+            })
+        };
+        let strategy = match name.to_lowercase().as_str() {
+            "hash" => match number(16)? {
+                n @ 4..=64 => Strategy::Hash(n),
+                _ => bail!("Hash length must be between 4 and 64"),
+            },
+            "fake" => Strategy::Fake,
+            "redact" => Strategy::Redact(arg.unwrap_or("***").to_string()),
+            "year-only" | "year" => Strategy::YearOnly,
+            "partial" => Strategy::Partial(number(4)?),
+            "null" | "empty" => Strategy::Null,
+            _ => bail!(
+                "Unsupported mask {:?}, expected hash, fake, redact, year-only, partial or null",
+                value
+            ),
+        };
+        Ok(strategy)
+    }
+
+    fn keyed(&self) -> bool {
+        matches!(self, Strategy::Hash(_) | Strategy::Fake)
+    }
+}
+
+/// Masks columns of a CSV file, all other fields are copied unchanged
+pub struct Masker {
+    rules: Vec<(usize, Strategy)>,
+    hasher: Option<Blake3>,
+    year: Regex,
+}
+
+impl Masker {
+    pub fn new(headers: &[String], opts: &MaskOptions) -> Result<Self> {
+        let mut rules: Vec<(usize, Strategy)> = Vec::with_capacity(opts.columns.len());
+        for rule in &opts.columns {
+            let (column, strategy) = rule
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Invalid mask {:?}, expected column=strategy", rule))?;
+            let column = column.trim();
+            let index = headers
+                .iter()
+                .position(|h| h == column)
+                .ok_or_else(|| anyhow!("Column {:?} not found", column))?;
+            if rules.iter().any(|(i, _)| *i == index) {
+                bail!("Column {:?} is masked more than once", column);
+            }
+            rules.push((index, Strategy::parse(strategy)?));
+        }
+
+        // 相同的密钥在不同文件中产生相同的假名
+        let hasher = match &opts.key {
+            Some(key) => Some(Blake3::load(key).context("Failed to load the mask key")?),
+            None if rules.iter().any(|(_, s)| s.keyed()) => {
+                bail!("A key file is required to hash or fake values")
+            }
+            None => None,
+        };
+        Ok(Self {
+            rules,
+            hasher,
+            year: Regex::new(r"\b(\d{4})\b")?,
+        })
+    }
+
+    pub fn apply(&self, record: &csv::StringRecord) -> csv::StringRecord {
+        let mut fields: Vec<String> = record.iter().map(str::to_string).collect();
+        for (index, strategy) in &self.rules {
+            if let Some(field) = fields.get_mut(*index) {
+                *field = self.mask(strategy, field);
+            }
+        }
+        csv::StringRecord::from(fields)
+    }
+
+    fn mask(&self, strategy: &Strategy, value: &str) -> String {
+        if value.is_empty() {
+            return String::new();
+        }
+        match strategy {
+            Strategy::Hash(len) => {
+                let hash = self.keyed_hash(value.as_bytes());
+                let hex: String = hash.iter().map(|b| format!("{:02x}", b)).collect();
+                hex[..*len].to_string()
+            }
+            Strategy::Fake => {
+                let mut rng = StdRng::from_seed(self.keyed_hash(value.as_bytes()));
+                fake(value, &mut rng)
+            }
+            Strategy::Redact(text) => text.clone(),
+            // 找不到年份时清空，避免泄露原值
+            Strategy::YearOnly => self
+                .year
+                .captures(value)
+                .map(|c| c[1].to_string())
+                .unwrap_or_default(),
+            Strategy::Partial(keep) => {
+                let len = value.chars().count();
+                value
+                    .chars()
+                    .enumerate()
+                    .map(|(i, c)| if i + keep < len { '*' } else { c })
+                    .collect()
+            }
+            Strategy::Null => String::new(),
+        }
+    }
+
+    fn keyed_hash(&self, data: &[u8]) -> [u8; 32] {
+        self.hasher
+            .as_ref()
+            .map(|hasher| hasher.hash(data))
+            .unwrap_or_default()
+    }
+}
+
+/// Replace letters and digits with random ones of the same class, keeping punctuation and spaces
+fn fake(value: &str, rng: &mut StdRng) -> String {
+    value
+        .chars()
+        .enumerate()
+        .map(|(i, c)| match c {
+            // 首位非零数字保持非零，避免改变数字位数
+            '1'..='9' if i == 0 => rng.gen_range(b'1'..=b'9') as char,
+            '0'..='9' => rng.gen_range(b'0'..=b'9') as char,
+            c if c.is_uppercase() => rng.gen_range(b'A'..=b'Z') as char,
+            c if c.is_alphabetic() => rng.gen_range(b'a'..=b'z') as char,
+            c => c,
+        })
+        .collect()
+}
+
+pub fn process_mask(input: &str, output: Box<dyn Write>, opts: &MaskOptions) -> Result<()> {
+    let (mut reader, dialect) = sniffed_csv_reader(input, &opts.dialect)?;
+    let headers = csv_headers(&mut reader)?;
+    let masker = Masker::new(&headers, opts)?;
+
+    // 按探测出的方言写出，没有表头的输入不添加 column_N 表头
+    let mut writer = dialect.writer_builder().from_writer(output);
+    if reader.has_headers() {
+        writer.write_record(&headers)?;
+    }
+    for record in reader.records() {
+        writer.write_record(&masker.apply(&record?))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::csv::writer::tests::SharedBuf;
+    use std::fs;
+
+    fn options(columns: &[&str], key: Option<&str>) -> MaskOptions {
+        MaskOptions {
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+            key: key.map(str::to_string),
+            dialect: CsvDialect::default(),
+        }
+    }
+
+    fn mask(input: &str, opts: &MaskOptions) -> Result<Vec<csv::StringRecord>> {
+        let buf = SharedBuf::default();
+        process_mask(input, Box::new(buf.clone()), opts)?;
+        let content = buf.0.borrow().clone();
+        let records = csv::Reader::from_reader(content.as_slice())
+            .records()
+            .collect::<Result<_, _>>()?;
+        Ok(records)
+    }
+
+    #[test]
+    fn test_mask_juventus() -> Result<()> {
+        let opts = options(
+            &[
+                "Name=hash",
+                "DOB=year-only",
+                "Nationality=redact",
+                "Kit Number=partial:1",
+            ],
+            Some("fixtures/blake3.key"),
+        );
+        let records = mask("assets/juventus.csv", &opts)?;
+        assert_eq!(records.len(), 27);
+        let buffon = &records[2];
+        assert_eq!(buffon[0].len(), 16);
+        assert_ne!(&buffon[0], "Gianluigi Buffon");
+        assert_eq!(&buffon[1], "Goalkeeper");
+        assert_eq!(&buffon[2], "1978");
+        assert_eq!(&buffon[3], "***");
+        assert_eq!(&buffon[4], "*7");
+
+        // 同一个密钥在其他文件中得到相同的假名
+        let opts = options(&["Player=hash"], Some("fixtures/blake3.key"));
+        let stats = mask("assets/juventus_stats.csv", &opts)?;
+        let ronaldo = records.iter().find(|r| r[2] == *"1985").unwrap();
+        assert!(stats.iter().any(|r| r[0] == ronaldo[0]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_fake_preserves_format() -> Result<()> {
+        let masker = Masker::new(
+            &["email".to_string()],
+            &options(&["email=fake"], Some("fixtures/blake3.key")),
+        )?;
+        let fake = masker.mask(&Strategy::Fake, "Gigi.Buffon77@juve.it");
+        assert_ne!(fake, "Gigi.Buffon77@juve.it");
+        assert_eq!(fake.len(), 21);
+        assert_eq!(&fake[4..5], ".");
+        assert_eq!(&fake[13..14], "@");
+        assert!(fake[..1].chars().all(|c| c.is_ascii_uppercase()));
+        assert!(fake[11..13].chars().all(|c| c.is_ascii_digit()));
+        assert_eq!(fake, masker.mask(&Strategy::Fake, "Gigi.Buffon77@juve.it"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_mask_sniffed_headerless() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("rcli-mask-{}.csv", std::process::id()));
+        fs::write(&path, "1;alpha;2.5\n2;beta;3.5\n3;gamma;4.5\n")?;
+        let opts = MaskOptions {
+            dialect: CsvDialect {
+                sniff: Some(10),
+                ..Default::default()
+            },
+            ..options(&["column_2=redact"], None)
+        };
+        let buf = SharedBuf::default();
+        process_mask(&path.to_string_lossy(), Box::new(buf.clone()), &opts)?;
+        fs::remove_file(path)?;
+        assert_eq!(
+            String::from_utf8(buf.0.borrow().clone())?,
+            "1;***;2.5\n2;***;3.5\n3;***;4.5\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_masks() {
+        let headers = vec!["Name".to_string()];
+        assert!(Masker::new(&headers, &options(&["Name=hash"], None)).is_err());
+        assert!(Masker::new(&headers, &options(&["Club=redact"], None)).is_err());
+        assert!(Masker::new(&headers, &options(&["Name=shuffle"], None)).is_err());
+        assert!(Masker::new(&headers, &options(&["Name=hash:100"], None)).is_err());
+    }
+}
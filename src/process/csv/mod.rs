@@ -0,0 +1,46 @@
+mod columnar;
+mod convert;
+mod database;
+mod dialect;
+mod diff;
+mod encoding;
+mod expr;
+mod filter;
+mod from_json;
+mod generate;
+mod join;
+mod mapping;
+mod mask;
+mod nested;
+mod parallel;
+mod query;
+mod reader;
+mod schema;
+mod show;
+mod split;
+mod sqlite;
+mod stats;
+mod transform;
+mod types;
+mod validate;
+mod workbook;
+mod writer;
+
+pub(crate) use convert::{process_csv, ConvertOptions};
+pub(crate) use database::{process_from_sqlite, process_to_sqlite, ToSqliteOptions};
+pub(crate) use dialect::CsvDialect;
+pub(crate) use diff::{process_diff, DiffOptions};
+pub(crate) use filter::FilterOptions;
+pub(crate) use from_json::process_json_to_csv;
+pub(crate) use generate::{process_csv_generate, GenerateOptions};
+pub(crate) use join::{process_join, JoinOptions};
+pub(crate) use mapping::Mapping;
+pub(crate) use mask::{process_mask, MaskOptions};
+pub(crate) use nested::FlattenOptions;
+pub(crate) use query::{process_query, QueryOptions};
+pub(crate) use show::{process_show, terminal_width, ShowOptions};
+pub(crate) use split::{process_split, SplitBy, SplitOptions};
+pub(crate) use stats::{process_stats, StatsOptions};
+pub(crate) use transform::TransformOptions;
+pub(crate) use validate::{process_validate, ValidateOptions};
+pub(crate) use writer::WriterOptions;
@@ -0,0 +1,309 @@
+use crate::cli::csv::ArrayMode;
+use anyhow::{anyhow, bail, Result};
+use serde_json::{Map, Value};
+
+pub struct FlattenOptions {
+    pub separator: String,
+    pub arrays: ArrayMode,
+    pub array_separator: String,
+}
+
+/// Flatten a record into one or more rows of dotted keys, exploding arrays may yield several rows
+pub fn flatten(record: &Value, opts: &FlattenOptions) -> Vec<Map<String, Value>> {
+    match record {
+        Value::Object(_) => flatten_value(record, "", opts, vec![Map::new()]),
+        _ => flatten_value(record, "value", opts, vec![Map::new()]),
+    }
+}
+
+fn flatten_value(
+    value: &Value,
+    key: &str,
+    opts: &FlattenOptions,
+    mut rows: Vec<Map<String, Value>>,
+) -> Vec<Map<String, Value>> {
+    match value {
+        Value::Object(object) => {
+            for (k, v) in object {
+                let key = if key.is_empty() {
+                    k.clone()
+                } else {
+                    format!("{}{}{}", key, opts.separator, k)
+                };
+                rows = flatten_value(v, &key, opts, rows);
+            }
+            rows
+        }
+        Value::Array(items) => match opts.arrays {
+            ArrayMode::Join => {
+                let joined = items
+                    .iter()
+                    .map(cell)
+                    .collect::<Vec<_>>()
+                    .join(&opts.array_separator);
+                set(rows, key, Value::String(joined))
+            }
+            ArrayMode::Index => {
+                for (i, item) in items.iter().enumerate() {
+                    rows = flatten_value(item, &format!("{}[{}]", key, i), opts, rows);
+                }
+                rows
+            }
+            ArrayMode::Explode if items.is_empty() => set(rows, key, Value::Null),
+            ArrayMode::Explode => rows
+                .into_iter()
+                .flat_map(|row| {
+                    items
+                        .iter()
+                        .flat_map(move |item| flatten_value(item, key, opts, vec![row.clone()]))
+                })
+                .collect(),
+        },
+        _ => set(rows, key, value.clone()),
+    }
+}
+
+fn set(mut rows: Vec<Map<String, Value>>, key: &str, value: Value) -> Vec<Map<String, Value>> {
+    for row in rows.iter_mut() {
+        row.insert(key.to_string(), value.clone());
+    }
+    rows
+}
+
+/// Highest array index accepted in a path, guards against huge allocations
+const MAX_INDEX: usize = 100_000;
+
+/// A step of a path like `players[0].name`
+#[derive(Debug, PartialEq)]
+enum Segment<'a> {
+    Key(&'a str),
+    Index(usize),
+}
+
+fn parse_path<'a>(path: &'a str, separator: &str) -> Vec<Segment<'a>> {
+    let parts: Vec<&str> = if separator.is_empty() {
+        vec![path]
+    } else {
+        path.split(separator).collect()
+    };
+    let mut segments = Vec::with_capacity(parts.len());
+    for part in parts {
+        // 从末尾取出 [N] 下标，括号中不是数字时整体作为键名
+        let mut key = part;
+        let mut indexes = Vec::new();
+        while let Some(rest) = key.strip_suffix(']') {
+            let Some((head, index)) = rest.rsplit_once('[') else {
+                break;
+            };
+            let Ok(index) = index.parse::<usize>() else {
+                break;
+            };
+            indexes.push(index);
+            key = head;
+        }
+        if !key.is_empty() || indexes.is_empty() {
+            segments.push(Segment::Key(key));
+        }
+        segments.extend(indexes.into_iter().rev().map(Segment::Index));
+    }
+    segments
+}
+
+/// Insert a value under a dotted path, creating the intermediate objects.
+/// `tags[0]` segments create arrays, missing elements are filled with null.
+pub fn insert_path(
+    object: &mut Map<String, Value>,
+    path: &str,
+    separator: &str,
+    value: Value,
+) -> Result<()> {
+    let segments = parse_path(path, separator);
+    let Some((Segment::Key(key), rest)) = segments.split_first() else {
+        bail!("Path {:?} must start with a key", path);
+    };
+    let slot = object.entry(key.to_string()).or_insert(Value::Null);
+    insert_segments(slot, rest, value).map_err(|e| anyhow!("Cannot insert {:?}: {}", path, e))
+}
+
+fn insert_segments(slot: &mut Value, segments: &[Segment], value: Value) -> Result<()> {
+    let Some((first, rest)) = segments.split_first() else {
+        if slot.is_object() || slot.is_array() {
+            bail!("the key is both a value and a container");
+        }
+        *slot = value;
+        return Ok(());
+    };
+    match first {
+        Segment::Key(key) => {
+            if slot.is_null() {
+                *slot = Value::Object(Map::new());
+            }
+            match slot {
+                Value::Object(child) => {
+                    let slot = child.entry(key.to_string()).or_insert(Value::Null);
+                    insert_segments(slot, rest, value)
+                }
+                _ => bail!("{:?} is nested under a value that is not an object", key),
+            }
+        }
+        Segment::Index(index) => {
+            if *index > MAX_INDEX {
+                bail!("array index {} is larger than {}", index, MAX_INDEX);
+            }
+            if slot.is_null() {
+                *slot = Value::Array(Vec::new());
+            }
+            match slot {
+                Value::Array(items) => {
+                    if items.len() <= *index {
+                        items.resize(index + 1, Value::Null);
+                    }
+                    insert_segments(&mut items[*index], rest, value)
+                }
+                _ => bail!("index {} is used on a value that is not an array", index),
+            }
+        }
+    }
+}
+
+/// Build nested objects and arrays from keys like `player.name` or `tags[0]`.
+/// Empty trailing array elements are dropped, so shorter lists do not end with nulls.
+pub fn unflatten(record: Value, separator: &str) -> Result<Value> {
+    let Value::Object(record) = record else {
+        return Ok(record);
+    };
+    let mut object = Map::with_capacity(record.len());
+    for (key, value) in record {
+        insert_path(&mut object, &key, separator, value)?;
+    }
+    let mut value = Value::Object(object);
+    trim_arrays(&mut value);
+    Ok(value)
+}
+
+fn trim_arrays(value: &mut Value) {
+    match value {
+        Value::Object(object) => object.values_mut().for_each(trim_arrays),
+        Value::Array(items) => {
+            items.iter_mut().for_each(trim_arrays);
+            while items.last().is_some_and(is_empty) {
+                items.pop();
+            }
+        }
+        _ => {}
+    }
+}
+
+fn is_empty(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::String(s) => s.is_empty(),
+        Value::Object(object) => object.values().all(is_empty),
+        _ => false,
+    }
+}
+
+/// Text of a value in a CSV cell, nested values are written as JSON
+pub fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        _ => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn opts(arrays: ArrayMode) -> FlattenOptions {
+        FlattenOptions {
+            separator: ".".into(),
+            arrays,
+            array_separator: ";".into(),
+        }
+    }
+
+    #[test]
+    fn test_flatten_nested_objects() {
+        let record = json!({"name": "Buffon", "address": {"city": "Turin", "zip": null}});
+        let rows = flatten(&record, &opts(ArrayMode::Join));
+        assert_eq!(
+            Value::Object(rows[0].clone()),
+            json!({"name": "Buffon", "address.city": "Turin", "address.zip": null})
+        );
+        let keys: Vec<_> = rows[0].keys().collect();
+        assert_eq!(keys, ["name", "address.city", "address.zip"]);
+    }
+
+    #[test]
+    fn test_flatten_arrays() {
+        let record = json!({"id": 1, "tags": ["a", "b"]});
+
+        let rows = flatten(&record, &opts(ArrayMode::Join));
+        assert_eq!(rows[0]["tags"], json!("a;b"));
+
+        let rows = flatten(&record, &opts(ArrayMode::Index));
+        assert_eq!(rows[0]["tags[0]"], json!("a"));
+        assert_eq!(rows[0]["tags[1]"], json!("b"));
+
+        let rows = flatten(&record, &opts(ArrayMode::Explode));
+        assert_eq!(rows.len(), 2);
+        assert_eq!(
+            Value::Object(rows[1].clone()),
+            json!({"id": 1, "tags": "b"})
+        );
+    }
+
+    #[test]
+    fn test_parse_path() {
+        use Segment::*;
+        assert_eq!(
+            parse_path("players[0].name", "."),
+            [Key("players"), Index(0), Key("name")]
+        );
+        assert_eq!(
+            parse_path("grid[1][2]", "."),
+            [Key("grid"), Index(1), Index(2)]
+        );
+        assert_eq!(parse_path("note[x]", "."), [Key("note[x]")]);
+        assert_eq!(parse_path("a__b", "__"), [Key("a"), Key("b")]);
+    }
+
+    #[test]
+    fn test_unflatten() -> Result<()> {
+        let record = json!({
+            "player.name": "Buffon",
+            "player.position": "Goalkeeper",
+            "stats.goals": 0,
+            "tags[0]": "captain",
+            "tags[1]": "veteran",
+            "tags[2]": "",
+            "clubs[0].name": "Parma",
+            "clubs[1].name": "Juventus",
+            "clubs[2].name": null,
+        });
+        assert_eq!(
+            unflatten(record, ".")?,
+            json!({
+                "player": {"name": "Buffon", "position": "Goalkeeper"},
+                "stats": {"goals": 0},
+                "tags": ["captain", "veteran"],
+                "clubs": [{"name": "Parma"}, {"name": "Juventus"}],
+            })
+        );
+
+        let record = json!({"player": "Buffon", "player.name": "Buffon"});
+        assert!(unflatten(record, ".").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_flatten_round_trip() -> Result<()> {
+        let record = json!({"id": 1, "team": {"name": "Juventus", "titles": [2012, 2013]}});
+        let rows = flatten(&record, &opts(ArrayMode::Index));
+        assert_eq!(unflatten(Value::Object(rows[0].clone()), ".")?, record);
+        Ok(())
+    }
+}
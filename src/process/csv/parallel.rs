@@ -0,0 +1,516 @@
+use crate::cli::csv::{ColumnType, ColumnTypes};
+use crate::process::csv::dialect::{csv_headers, decoded_input, CsvDialect};
+use crate::process::csv::types::{declared_types, RecordConverter, TypeInference};
+use crate::process::csv::workbook::is_workbook;
+use anyhow::{anyhow, bail, Result};
+use csv::StringRecord;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::io::Read;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+
+/// Target size of the chunks handed to the workers
+const CHUNK_SIZE: usize = 1 << 20;
+/// Chunks queued per worker, bounds the memory held by the pipeline
+const CHUNKS_PER_THREAD: usize = 4;
+
+/// A run of whole records and where it starts in the input
+pub struct Chunk {
+    data: Vec<u8>,
+    /// Line number of the first byte, starting at 1
+    line: u64,
+    byte: u64,
+}
+
+/// Where the scanner is inside the CSV grammar, mirrors the states of the csv parser
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    StartRecord,
+    StartField,
+    InField,
+    InQuoted,
+    /// After an escape character inside a quoted field
+    InEscape,
+    /// After a quote inside a quoted field, either a doubled quote or the end of the field
+    InQuotedQuote,
+    InComment,
+}
+
+/// Cuts the input into chunks of whole records. Newlines inside quoted fields do not end a record,
+/// so every chunk can be parsed on its own.
+pub struct RecordChunks {
+    reader: Box<dyn Read>,
+    dialect: CsvDialect,
+    chunk_size: usize,
+    state: State,
+    buffer: Vec<u8>,
+    /// Bytes of the buffer already scanned
+    scanned: usize,
+    /// End of the last complete record in the buffer
+    boundary: usize,
+    line: u64,
+    byte: u64,
+    eof: bool,
+    /// A chunk put back in front of the input
+    front: Option<Chunk>,
+}
+
+impl RecordChunks {
+    pub fn new(reader: Box<dyn Read>, dialect: &CsvDialect, chunk_size: usize) -> Self {
+        Self {
+            reader,
+            dialect: dialect.clone(),
+            chunk_size: chunk_size.max(1),
+            state: State::StartRecord,
+            buffer: Vec::new(),
+            scanned: 0,
+            boundary: 0,
+            line: 1,
+            byte: 0,
+            eof: false,
+            front: None,
+        }
+    }
+
+    fn next_chunk(&mut self) -> Result<Option<Chunk>> {
+        if let Some(chunk) = self.front.take() {
+            return Ok(Some(chunk));
+        }
+        // 读到足够的数据且其中有完整记录为止，单条记录超过块大小时继续读取
+        while !self.eof && (self.buffer.len() < self.chunk_size || self.boundary == 0) {
+            let start = self.buffer.len();
+            let want = self.chunk_size.max(start + 1);
+            self.buffer.resize(want, 0);
+            let n = self.reader.read(&mut self.buffer[start..])?;
+            self.buffer.truncate(start + n);
+            self.eof = n == 0;
+            self.scan();
+        }
+
+        let end = match self.eof {
+            true => self.buffer.len(),
+            false => self.boundary,
+        };
+        if end == 0 {
+            return Ok(None);
+        }
+        let rest = self.buffer.split_off(end);
+        let data = std::mem::replace(&mut self.buffer, rest);
+        self.scanned -= end;
+        self.boundary = 0;
+
+        let chunk = Chunk {
+            line: self.line,
+            byte: self.byte,
+            data,
+        };
+        self.line += chunk.data.iter().filter(|&&b| b == b'\n').count() as u64;
+        self.byte += chunk.data.len() as u64;
+        Ok(Some(chunk))
+    }
+
+    fn scan(&mut self) {
+        let CsvDialect {
+            delimiter,
+            quote,
+            escape,
+            comment,
+            ..
+        } = self.dialect;
+        let terminator = |b: u8| b == b'\n' || b == b'\r';
+        for (i, &b) in self.buffer.iter().enumerate().skip(self.scanned) {
+            self.state = match self.state {
+                State::StartRecord if Some(b) == comment => State::InComment,
+                State::StartRecord | State::StartField if b == quote => State::InQuoted,
+                State::StartRecord if terminator(b) => State::StartRecord,
+                State::InComment if b == b'\n' => State::StartRecord,
+                State::InComment => State::InComment,
+                State::InQuoted if Some(b) == escape => State::InEscape,
+                State::InQuoted if b == quote => State::InQuotedQuote,
+                State::InQuoted => State::InQuoted,
+                State::InEscape => State::InQuoted,
+                State::InQuotedQuote if b == quote && escape.is_none() => State::InQuoted,
+                _ if b == delimiter => State::StartField,
+                _ if terminator(b) => State::StartRecord,
+                _ => State::InField,
+            };
+            // 只在换行符处切分，避免把 \r\n 拆到两个块中
+            if b == b'\n' && self.state == State::StartRecord {
+                self.boundary = i + 1;
+            }
+        }
+        self.scanned = self.buffer.len();
+    }
+}
+
+impl Iterator for RecordChunks {
+    type Item = Result<Chunk>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_chunk().transpose()
+    }
+}
+
+/// Runs a function over the chunks on a thread pool and returns the results in input order.
+/// Reading the next chunks overlaps with the work on the previous ones.
+struct OrderedMap<T> {
+    chunks: RecordChunks,
+    pool: Arc<ThreadPool>,
+    f: Arc<dyn Fn(Chunk) -> T + Send + Sync>,
+    pending: VecDeque<Receiver<T>>,
+    error: Option<anyhow::Error>,
+}
+
+impl<T: Send + 'static> OrderedMap<T> {
+    fn new(
+        chunks: RecordChunks,
+        pool: Arc<ThreadPool>,
+        f: impl Fn(Chunk) -> T + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            chunks,
+            pool,
+            f: Arc::new(f),
+            pending: VecDeque::new(),
+            error: None,
+        }
+    }
+}
+
+impl<T: Send + 'static> Iterator for OrderedMap<T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let limit = self.pool.current_num_threads() * CHUNKS_PER_THREAD;
+        while self.error.is_none() && self.pending.len() < limit {
+            match self.chunks.next() {
+                Some(Ok(chunk)) => {
+                    let (tx, rx) = mpsc::sync_channel(1);
+                    let f = self.f.clone();
+                    self.pool.spawn(move || {
+                        let _ = tx.send(f(chunk));
+                    });
+                    self.pending.push_back(rx);
+                }
+                Some(Err(e)) => self.error = Some(e),
+                None => break,
+            }
+        }
+        // 读取错误排在已提交的块之后返回
+        match self.pending.pop_front() {
+            Some(rx) => Some(rx.recv().map_err(|_| anyhow!("A worker thread failed"))),
+            None => self.error.take().map(Err),
+        }
+    }
+}
+
+/// Reads CSV records as JSON objects like `TypedReader`, parsing and converting chunks of
+/// the input on a thread pool. Records come out in the same order and with the same values.
+pub struct ParallelReader {
+    converter: Arc<RecordConverter>,
+    dialect: CsvDialect,
+    chunks: RecordChunks,
+    pool: Arc<ThreadPool>,
+}
+
+impl ParallelReader {
+    /// Stdin cannot be read twice for type inference and workbooks are not CSV text
+    pub fn supports(input: &str) -> bool {
+        input != "-" && !is_workbook(input)
+    }
+
+    /// Open the input with `threads` workers, 0 uses one per CPU
+    pub fn open(
+        input: &str,
+        dialect: &CsvDialect,
+        infer: bool,
+        overrides: &ColumnTypes,
+        threads: usize,
+    ) -> Result<Self> {
+        let pool = Arc::new(ThreadPoolBuilder::new().num_threads(threads).build()?);
+        let (headers, dialect, chunks) = open_chunks(input, dialect)?;
+
+        let types = if infer {
+            let (_, _, sample) = open_chunks(input, &dialect)?;
+            let worker = dialect.clone();
+            let len = headers.len();
+            let mut inference = TypeInference::default();
+            for part in OrderedMap::new(sample, pool.clone(), move |chunk| {
+                let mut inference = TypeInference::default();
+                // 与顺序推断一样，非 flexible 方言下字段数不一致的记录在写出前就报错
+                for record in chunk_reader(&chunk, &worker).into_records() {
+                    let record = locate(record?, &chunk);
+                    check_length(&record, len, &worker)?;
+                    inference.update(&record);
+                }
+                Ok::<_, anyhow::Error>(inference)
+            }) {
+                inference.merge(part??);
+            }
+            inference.types(&headers, overrides)
+        } else {
+            declared_types(&headers, overrides)
+        };
+
+        Ok(Self {
+            converter: Arc::new(RecordConverter::new(headers, types, overrides)),
+            dialect,
+            chunks,
+            pool,
+        })
+    }
+
+    pub fn headers(&self) -> &[String] {
+        self.converter.headers()
+    }
+
+    pub fn types(&self) -> &[ColumnType] {
+        self.converter.types()
+    }
+
+    /// Converted records passed through `f` on the workers, records mapped to `None` are dropped
+    pub fn records<F>(self, f: F) -> impl Iterator<Item = Result<Value>>
+    where
+        F: Fn(Value) -> Result<Option<Value>> + Send + Sync + 'static,
+    {
+        let Self {
+            converter,
+            dialect,
+            chunks,
+            pool,
+        } = self;
+        OrderedMap::new(chunks, pool, move |chunk| {
+            let mut values = Vec::new();
+            for result in chunk_reader(&chunk, &dialect).into_records() {
+                let value = result
+                    .map_err(anyhow::Error::from)
+                    .and_then(|record| {
+                        let record = locate(record, &chunk);
+                        check_length(&record, converter.headers().len(), &dialect)?;
+                        converter.convert(&record)
+                    })
+                    .and_then(&f);
+                match value {
+                    Ok(Some(value)) => values.push(Ok(value)),
+                    Ok(None) => {}
+                    // 与顺序处理一致，遇到第一个错误即停止
+                    Err(e) => {
+                        values.push(Err(e));
+                        break;
+                    }
+                }
+            }
+            values
+        })
+        .flat_map(|result| match result {
+            Ok(values) => values,
+            Err(e) => vec![Err(e)],
+        })
+    }
+}
+
+/// Headers, the dialect after sniffing and the chunks of the records after the header
+fn open_chunks(
+    input: &str,
+    dialect: &CsvDialect,
+) -> Result<(Vec<String>, CsvDialect, RecordChunks)> {
+    let (reader, dialect) = decoded_input(input, dialect)?;
+    let mut chunks = RecordChunks::new(reader, &dialect, CHUNK_SIZE);
+    // 空输入与顺序读取一样得到空表头
+    let mut first = chunks.next_chunk()?.unwrap_or(Chunk {
+        data: Vec::new(),
+        line: 1,
+        byte: 0,
+    });
+
+    let mut reader = dialect.builder().from_reader(first.data.as_slice());
+    let headers = csv_headers(&mut reader)?;
+    if dialect.has_header {
+        // 表头单独解析，块中只保留数据记录
+        let position = reader.position();
+        let (byte, line) = (position.byte(), position.line());
+        first.data.drain(..byte as usize);
+        first.byte += byte;
+        first.line += line - 1;
+    }
+    chunks.front = Some(first);
+    Ok((headers, dialect, chunks))
+}
+
+fn chunk_reader<'a>(chunk: &'a Chunk, dialect: &CsvDialect) -> csv::Reader<&'a [u8]> {
+    let mut builder = dialect.builder();
+    builder.has_headers(false).flexible(true);
+    builder.from_reader(chunk.data.as_slice())
+}
+
+/// Move the position of a record from its chunk to the whole input
+fn locate(mut record: StringRecord, chunk: &Chunk) -> StringRecord {
+    if let Some(position) = record.position() {
+        let mut moved = position.clone();
+        moved
+            .set_line(position.line() + chunk.line - 1)
+            .set_byte(position.byte() + chunk.byte);
+        record.set_position(Some(moved));
+    }
+    record
+}
+
+/// Chunks are parsed independently, so records are checked against the header instead of
+/// the first record of the chunk
+fn check_length(record: &StringRecord, len: usize, dialect: &CsvDialect) -> Result<()> {
+    if dialect.flexible || record.len() == len {
+        return Ok(());
+    }
+    let line = record.position().map_or(0, |p| p.line());
+    bail!(
+        "Line {}: found record with {} fields, but the header has {} fields",
+        line,
+        record.len(),
+        len
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::csv::OutputFormat;
+    use crate::process::csv::convert::{process_csv, ConvertOptions};
+    use crate::process::csv::filter::FilterOptions;
+    use crate::process::csv::writer::WriterOptions;
+    use std::fs;
+    use std::io::Cursor;
+
+    fn parse(data: &[u8], dialect: &CsvDialect) -> Vec<StringRecord> {
+        let mut builder = dialect.builder();
+        builder.has_headers(false).flexible(true);
+        builder
+            .from_reader(data)
+            .into_records()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_chunks_end_at_record_boundaries() -> Result<()> {
+        let data = b"name,note\r\nalice,\"multi\nline, \"\"quoted\"\"\"\n# a comment with \"\nbob,plain\n\ncarol,\"a\"\"\nb\"\ndave,last";
+        let dialect = CsvDialect {
+            comment: Some(b'#'),
+            ..Default::default()
+        };
+        let expected = parse(data, &dialect);
+        for size in 1..=data.len() {
+            let reader = Box::new(Cursor::new(data.to_vec()));
+            let chunks = RecordChunks::new(reader, &dialect, size).collect::<Result<Vec<_>>>()?;
+            let joined: Vec<u8> = chunks.iter().flat_map(|c| c.data.clone()).collect();
+            assert_eq!(joined, data);
+
+            let records: Vec<_> = chunks
+                .iter()
+                .flat_map(|chunk| parse(&chunk.data, &dialect))
+                .collect();
+            assert_eq!(records, expected, "chunk size {}", size);
+            for chunk in &chunks {
+                let line = 1 + data[..chunk.byte as usize]
+                    .iter()
+                    .filter(|&&b| b == b'\n')
+                    .count() as u64;
+                assert_eq!(chunk.line, line);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parallel_output_is_identical() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("rcli-parallel-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let input = dir.join("input.csv");
+        let mut content = String::from("id,name,score,note,joined\n");
+        // 超过3个块，并让带引号的换行跨越块边界
+        for i in 0..40_000 {
+            content.push_str(&format!(
+                "{},player {},{}.5,\"line one\nline \"\"{}\"\"\",2024-01-{:02}\n",
+                i,
+                i,
+                i % 97,
+                i,
+                i % 28 + 1
+            ));
+        }
+        fs::write(&input, content)?;
+
+        let convert = |format: OutputFormat,
+                       filter: &FilterOptions,
+                       threads: Option<usize>|
+         -> Result<Vec<u8>> {
+            let output = dir.join(format!("{:?}-{:?}", format, threads));
+            let opts = ConvertOptions {
+                format,
+                dialect: CsvDialect::default(),
+                infer: true,
+                types: ColumnTypes::default(),
+                transform: Default::default(),
+                filter: filter.clone(),
+                mapping: None,
+                unflatten: None,
+                writer: WriterOptions::default(),
+                threads,
+            };
+            let input = input.to_string_lossy();
+            process_csv(&input, output.to_string_lossy().to_string(), &opts)?;
+            Ok(fs::read(output)?)
+        };
+        let none = FilterOptions::default();
+        for format in [OutputFormat::Ndjson, OutputFormat::Csv] {
+            assert_eq!(
+                convert(format, &none, Some(3))?,
+                convert(format, &none, None)?
+            );
+        }
+        // 过滤和选择列在工作线程上执行，结果与顺序处理相同
+        let filter = FilterOptions {
+            select: vec!["id".into(), "score".into()],
+            filter: Some("score > 50".into()),
+            ..Default::default()
+        };
+        let json = OutputFormat::Json;
+        assert_eq!(
+            convert(json, &filter, Some(3))?,
+            convert(json, &filter, None)?
+        );
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_parallel_ragged_rows_fail_before_writing() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("rcli-ragged-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let input = dir.join("input.csv");
+        fs::write(&input, "a,b\n1,2\n3\n")?;
+        let output = dir.join("output.json");
+        let opts = ConvertOptions {
+            format: OutputFormat::Json,
+            dialect: CsvDialect::default(),
+            infer: true,
+            types: ColumnTypes::default(),
+            transform: Default::default(),
+            filter: Default::default(),
+            mapping: None,
+            unflatten: None,
+            writer: WriterOptions::default(),
+            threads: Some(2),
+        };
+        let ret = process_csv(
+            &input.to_string_lossy(),
+            output.to_string_lossy().to_string(),
+            &opts,
+        );
+        assert!(ret.unwrap_err().to_string().contains("Line 3"));
+        assert!(!output.exists());
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,83 @@
+use crate::cli::csv::{ColumnTypes, OutputFormat};
+use crate::process::csv::dialect::CsvDialect;
+use crate::process::csv::reader::TypedReader;
+use crate::process::csv::sqlite::{create_table, insert_records, query_records, table_name};
+use crate::process::csv::writer::{record_writer, WriterOptions};
+use anyhow::{bail, Result};
+use rusqlite::Connection;
+use std::collections::HashSet;
+use std::io::Write;
+use tracing::info;
+
+pub struct QueryOptions {
+    pub format: OutputFormat,
+    pub dialect: CsvDialect,
+    pub types: ColumnTypes,
+}
+
+/// Load every input into an in-memory SQLite table named after the file and run the query
+pub fn process_query(
+    sql: &str,
+    inputs: &[String],
+    output: Box<dyn Write>,
+    opts: &QueryOptions,
+) -> Result<()> {
+    let mut conn = Connection::open_in_memory()?;
+    let mut tables = HashSet::new();
+    for input in inputs {
+        let table = table_name(input);
+        if !tables.insert(table.clone()) {
+            bail!("Duplicate table name {:?} for input {:?}", table, input);
+        }
+
+        let reader = TypedReader::open(input, &opts.dialect, true, &opts.types)?;
+        let headers = reader.headers().to_vec();
+        let tx = conn.transaction()?;
+        create_table(&tx, &table, &headers, reader.types())?;
+        let count = insert_records(&tx, &table, &headers, reader.records())?;
+        tx.commit()?;
+        info!(
+            "Loaded {} rows from {:?} into table {}",
+            count, input, table
+        );
+    }
+
+    let mut writer = record_writer(opts.format, output, &WriterOptions::default());
+    query_records(&conn, sql, |record| writer.write(&record))?;
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::csv::writer::tests::SharedBuf;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn test_query_group_by() -> Result<()> {
+        let buf = SharedBuf::default();
+        let opts = QueryOptions {
+            format: OutputFormat::Json,
+            dialect: CsvDialect::default(),
+            types: ColumnTypes::default(),
+        };
+        let sql = "SELECT Nationality, COUNT(*) AS players, MAX(\"Kit Number\") AS max_kit \
+                   FROM juventus GROUP BY Nationality ORDER BY players DESC, Nationality LIMIT 2";
+        process_query(
+            sql,
+            &["assets/juventus.csv".to_string()],
+            Box::new(buf.clone()),
+            &opts,
+        )?;
+
+        let ret: Value = serde_json::from_slice(&buf.0.borrow())?;
+        assert_eq!(
+            ret,
+            json!([
+                {"Nationality": "Italy", "players": 8, "max_kit": 77},
+                {"Nationality": "Brazil", "players": 3, "max_kit": 13},
+            ])
+        );
+        Ok(())
+    }
+}
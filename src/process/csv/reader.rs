@@ -0,0 +1,77 @@
+use crate::cli::csv::{ColumnType, ColumnTypes};
+use crate::process::csv::dialect::{csv_headers, csv_reader, CsvDialect};
+use crate::process::csv::types::{declared_types, RecordConverter, TypeInference};
+use anyhow::Result;
+use csv::{Reader, StringRecord};
+use serde_json::Value;
+use std::io::Read;
+
+/// Number of records used for type inference when the input cannot be read twice
+const INFER_SAMPLE_SIZE: usize = 1000;
+
+/// Reads CSV records as JSON objects with typed values
+pub struct TypedReader {
+    converter: RecordConverter,
+    sample: Vec<StringRecord>,
+    reader: Reader<Box<dyn Read>>,
+}
+
+impl TypedReader {
+    pub fn open(
+        input: &str,
+        dialect: &CsvDialect,
+        infer: bool,
+        overrides: &ColumnTypes,
+    ) -> Result<Self> {
+        let mut reader = csv_reader(input, dialect)?;
+        let headers = csv_headers(&mut reader)?;
+
+        let mut sample = Vec::new();
+        let types = if infer {
+            let mut inference = TypeInference::default();
+            if input == "-" {
+                // stdin 只能读取一次，缓存前N条记录用于推断
+                for result in reader.records().take(INFER_SAMPLE_SIZE) {
+                    let record = result?;
+                    inference.update(&record);
+                    sample.push(record);
+                }
+            } else {
+                // 文件先完整扫描一遍推断类型，第二遍再转换，内存占用与文件大小无关
+                for result in csv_reader(input, dialect)?.records() {
+                    inference.update(&result?);
+                }
+            }
+            inference.types(&headers, overrides)
+        } else {
+            declared_types(&headers, overrides)
+        };
+
+        Ok(Self {
+            converter: RecordConverter::new(headers, types, overrides),
+            sample,
+            reader,
+        })
+    }
+
+    pub fn headers(&self) -> &[String] {
+        self.converter.headers()
+    }
+
+    pub fn types(&self) -> &[ColumnType] {
+        self.converter.types()
+    }
+
+    pub fn records(self) -> impl Iterator<Item = Result<Value>> {
+        let Self {
+            converter,
+            sample,
+            reader,
+        } = self;
+        sample
+            .into_iter()
+            .map(Ok)
+            .chain(reader.into_records())
+            .map(move |result| converter.convert(&result?))
+    }
+}
@@ -0,0 +1,111 @@
+use crate::cli::csv::ColumnType;
+use anyhow::{anyhow, Context, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+/// Column definitions a CSV file is expected to follow
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Schema {
+    pub columns: Vec<ColumnSchema>,
+    /// Reject columns that are not declared in the schema
+    #[serde(default)]
+    pub strict: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ColumnSchema {
+    pub name: String,
+    #[serde(rename = "type", default = "default_type")]
+    pub ty: ColumnType,
+    #[serde(default)]
+    pub required: bool,
+    #[serde(default)]
+    pub unique: bool,
+    /// Regular expression the whole value has to match
+    pub pattern: Option<String>,
+    #[serde(rename = "enum")]
+    pub values: Option<Vec<String>>,
+    pub min: Option<Value>,
+    pub max: Option<Value>,
+    /// Kind of fake data for `csv generate`, guessed from the column name when missing
+    pub generator: Option<Generator>,
+}
+
+/// Fake data generators, integers, floats, dates and enums are generated from the type and range
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Generator {
+    Name,
+    FirstName,
+    LastName,
+    Email,
+    Uuid,
+    Country,
+    City,
+    Phone,
+    Company,
+    Word,
+    Sentence,
+    /// Increasing integers starting at `min`
+    Sequence,
+}
+
+fn default_type() -> ColumnType {
+    ColumnType::String
+}
+
+impl Schema {
+    pub fn load(path: &str) -> Result<Self> {
+        load_config(path)
+    }
+}
+
+/// Load a schema or mapping from a YAML, JSON or TOML file, chosen by the file extension
+pub fn load_config<T: DeserializeOwned>(path: &str) -> Result<T> {
+    let content = fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+    let extension = Path::new(path)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase());
+    let config = match extension.as_deref() {
+        Some("json") => serde_json::from_str(&content)?,
+        Some("toml") => toml::from_str(&content)?,
+        Some("yaml" | "yml") => serde_yaml::from_str(&content)?,
+        _ => {
+            return Err(anyhow!(
+                "Unsupported file {:?}, use .yaml, .json or .toml",
+                path
+            ))
+        }
+    };
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schema_formats_are_equivalent() -> Result<()> {
+        let yaml: Schema = serde_yaml::from_str(
+            "columns:\n  - name: Kit Number\n    type: int\n    required: true\n    min: 1\n",
+        )?;
+        let json: Schema = serde_json::from_str(
+            r#"{"columns": [{"name": "Kit Number", "type": "int", "required": true, "min": 1}]}"#,
+        )?;
+        let toml: Schema = toml::from_str(
+            "[[columns]]\nname = \"Kit Number\"\ntype = \"int\"\nrequired = true\nmin = 1\n",
+        )?;
+        for schema in [yaml, json, toml] {
+            let column = &schema.columns[0];
+            assert_eq!(column.ty, ColumnType::Integer);
+            assert!(column.required && !column.unique);
+            assert_eq!(column.min, Some(Value::from(1)));
+        }
+        Ok(())
+    }
+}
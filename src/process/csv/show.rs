@@ -0,0 +1,230 @@
+use crate::process::csv::dialect::{csv_headers, csv_reader, CsvDialect};
+use anyhow::{anyhow, Result};
+use std::collections::VecDeque;
+use std::io::{self, BufRead, IsTerminal, Write};
+use terminal_size::{terminal_size, Height, Width};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+const DEFAULT_WIDTH: usize = 120;
+const DEFAULT_HEIGHT: usize = 40;
+const MIN_COLUMN_WIDTH: usize = 3;
+
+pub struct ShowOptions {
+    pub dialect: CsvDialect,
+    pub head: Option<usize>,
+    pub tail: Option<usize>,
+    pub columns: Vec<String>,
+    pub width: Option<usize>,
+    pub pager: bool,
+}
+
+pub fn process_show(input: &str, opts: &ShowOptions) -> Result<()> {
+    let mut reader = csv_reader(input, &opts.dialect)?;
+    let headers = csv_headers(&mut reader)?;
+
+    let indices = if opts.columns.is_empty() {
+        (0..headers.len()).collect::<Vec<_>>()
+    } else {
+        opts.columns
+            .iter()
+            .map(|name| {
+                headers
+                    .iter()
+                    .position(|h| h == name)
+                    .ok_or_else(|| anyhow!("Column {:?} not found", name))
+            })
+            .collect::<Result<_>>()?
+    };
+    let headers: Vec<String> = indices.iter().map(|&i| headers[i].clone()).collect();
+
+    let records = reader.into_records().map(|result| {
+        result.map(|record| {
+            indices
+                .iter()
+                .map(|&i| record.get(i).unwrap_or_default().to_string())
+                .collect::<Vec<_>>()
+        })
+    });
+    let records: Box<dyn Iterator<Item = _>> = match opts.head {
+        Some(n) => Box::new(records.take(n)),
+        None => Box::new(records),
+    };
+    let records: Box<dyn Iterator<Item = _>> = match opts.tail {
+        Some(n) => {
+            // 只保留最后N条记录
+            let mut last = VecDeque::with_capacity(n);
+            for record in records {
+                if last.len() == n {
+                    last.pop_front();
+                }
+                last.push_back(record?);
+            }
+            Box::new(last.into_iter().map(Ok))
+        }
+        None => records,
+    };
+
+    let width = opts.width.unwrap_or_else(terminal_width);
+
+    // 标准输入被CSV占用或输出不是终端时不分页
+    let paging = opts.pager && input != "-" && io::stdout().is_terminal();
+    if !paging {
+        let rows = records.collect::<Result<Vec<_>, _>>()?;
+        print!("{}", render_table(&headers, &rows, width));
+        return Ok(());
+    }
+
+    // 留出表头、边框和提示所占的行
+    let term_height = terminal_size().map_or(DEFAULT_HEIGHT, |(_, Height(h))| h as usize);
+    let page_size = term_height.saturating_sub(6).max(1);
+    let mut records = records.peekable();
+    let mut stdin = io::stdin().lock();
+    loop {
+        let rows = records
+            .by_ref()
+            .take(page_size)
+            .collect::<Result<Vec<_>, _>>()?;
+        print!("{}", render_table(&headers, &rows, width));
+        if records.peek().is_none() {
+            break;
+        }
+
+        eprint!("-- More -- (Enter: next page, q: quit) ");
+        io::stderr().flush()?;
+        let mut answer = String::new();
+        if stdin.read_line(&mut answer)? == 0 || answer.trim().eq_ignore_ascii_case("q") {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn terminal_width() -> usize {
+    terminal_size().map_or(DEFAULT_WIDTH, |(Width(w), _)| w as usize)
+}
+
+/// Render rows as a Unicode box table no wider than `max_width`
+pub fn render_table(headers: &[String], rows: &[Vec<String>], max_width: usize) -> String {
+    let headers: Vec<String> = headers.iter().map(|h| clean(h)).collect();
+    let rows: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| row.iter().map(|c| clean(c)).collect())
+        .collect();
+
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.width()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.width());
+        }
+    }
+    // 超出终端宽度时，每次收窄当前最宽的列
+    let overhead = 3 * widths.len() + 1;
+    while widths.iter().sum::<usize>() + overhead > max_width {
+        match widths.iter_mut().max() {
+            Some(widest) if *widest > MIN_COLUMN_WIDTH => *widest -= 1,
+            _ => break,
+        }
+    }
+    let numeric: Vec<bool> = (0..widths.len())
+        .map(|i| {
+            rows.iter()
+                .filter_map(|row| row.get(i))
+                .filter(|cell| !cell.is_empty())
+                .all(|cell| cell.parse::<f64>().is_ok())
+        })
+        .collect();
+
+    let border = |left: &str, mid: &str, right: &str| {
+        let line = widths
+            .iter()
+            .map(|w| "─".repeat(w + 2))
+            .collect::<Vec<_>>()
+            .join(mid);
+        format!("{}{}{}\n", left, line, right)
+    };
+    let line = |cells: &[String], align: &[bool]| {
+        let cells = widths
+            .iter()
+            .enumerate()
+            .map(|(i, &w)| {
+                let cell = truncate(cells.get(i).map_or("", |c| c.as_str()), w);
+                let pad = " ".repeat(w - cell.width());
+                if align[i] {
+                    format!(" {}{} ", pad, cell)
+                } else {
+                    format!(" {}{} ", cell, pad)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("│");
+        format!("│{}│\n", cells)
+    };
+
+    let mut table = border("┌", "┬", "┐");
+    table.push_str(&line(&headers, &vec![false; widths.len()]));
+    table.push_str(&border("├", "┼", "┤"));
+    for row in &rows {
+        table.push_str(&line(row, &numeric));
+    }
+    table.push_str(&border("└", "┴", "┘"));
+    table
+}
+
+/// 换行和制表符会破坏表格布局，替换为空格
+fn clean(cell: &str) -> String {
+    cell.chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect()
+}
+
+fn truncate(cell: &str, width: usize) -> String {
+    if cell.width() <= width {
+        return cell.to_string();
+    }
+    let mut ret = String::new();
+    let mut used = 0;
+    for c in cell.chars() {
+        let w = c.width().unwrap_or(0);
+        if used + w + 1 > width {
+            break;
+        }
+        ret.push(c);
+        used += w;
+    }
+    ret.push('…');
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_table() {
+        let headers = vec!["Name".to_string(), "Kit".to_string()];
+        let rows = vec![
+            vec!["Buffon".to_string(), "77".to_string()],
+            vec!["Szczesny".to_string(), "1".to_string()],
+        ];
+        let table = render_table(&headers, &rows, 80);
+        assert_eq!(
+            table,
+            "┌──────────┬─────┐\n\
+             │ Name     │ Kit │\n\
+             ├──────────┼─────┤\n\
+             │ Buffon   │  77 │\n\
+             │ Szczesny │   1 │\n\
+             └──────────┴─────┘\n"
+        );
+    }
+
+    #[test]
+    fn test_render_table_truncates_wide_cells() {
+        let headers = vec!["Name".to_string()];
+        let rows = vec![vec!["Wojciech Szczesny".to_string()]];
+        let table = render_table(&headers, &rows, 12);
+        assert!(table.contains("│ Wojciec… │"));
+        assert!(table.lines().all(|line| line.width() <= 12));
+    }
+}
@@ -0,0 +1,362 @@
+use crate::process::csv::dialect::{csv_headers, sniffed_csv_reader, CsvDialect};
+use anyhow::{bail, Context, Result};
+use csv::{ByteRecord, Writer};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+/// Partition files kept open at the same time when splitting by a column, the least recently
+/// used one is closed and later reopened for appending
+#[cfg(not(test))]
+const MAX_OPEN_FILES: usize = 128;
+#[cfg(test)]
+const MAX_OPEN_FILES: usize = 4;
+
+/// How records are assigned to chunks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SplitBy {
+    /// At most N records per chunk
+    Rows(usize),
+    /// Roughly N bytes per chunk, a chunk always holds at least one record
+    Bytes(u64),
+    /// One chunk per distinct value of the column
+    Column(String),
+}
+
+pub struct SplitOptions {
+    pub by: SplitBy,
+    pub dialect: CsvDialect,
+    /// File name template with `{stem}`, `{index}` and `{value}` placeholders
+    pub template: Option<String>,
+    pub output_dir: String,
+}
+
+/// A chunk file that is being written
+struct Chunk {
+    path: PathBuf,
+    /// Closed while other partitions use the open files
+    writer: Option<Writer<BufWriter<File>>>,
+    rows: usize,
+    bytes: u64,
+    /// When the chunk was last written to, for closing the least recently used partition
+    used: u64,
+}
+
+/// One chunk per distinct column value, at most `MAX_OPEN_FILES` of them open
+#[derive(Default)]
+struct Partitions {
+    chunks: Vec<Chunk>,
+    index: HashMap<Vec<u8>, usize>,
+    open: usize,
+    tick: u64,
+}
+
+impl Partitions {
+    fn get(&mut self, value: &[u8], splitter: &mut Splitter) -> Result<&mut Chunk> {
+        let n = match self.index.get(value) {
+            Some(&n) => n,
+            None => {
+                self.make_room()?;
+                let name = sanitize(&String::from_utf8_lossy(value));
+                self.chunks
+                    .push(splitter.create(self.chunks.len() + 1, &name)?);
+                self.open += 1;
+                self.index.insert(value.to_vec(), self.chunks.len() - 1);
+                self.chunks.len() - 1
+            }
+        };
+        if self.chunks[n].writer.is_none() {
+            self.make_room()?;
+            self.chunks[n].writer = Some(splitter.append(&self.chunks[n].path)?);
+            self.open += 1;
+        }
+        self.tick += 1;
+        let chunk = &mut self.chunks[n];
+        chunk.used = self.tick;
+        Ok(chunk)
+    }
+
+    fn make_room(&mut self) -> Result<()> {
+        if self.open < MAX_OPEN_FILES {
+            return Ok(());
+        }
+        let lru = self
+            .chunks
+            .iter_mut()
+            .filter(|c| c.writer.is_some())
+            .min_by_key(|c| c.used);
+        if let Some(writer) = lru.and_then(|c| c.writer.take()) {
+            writer.into_inner()?;
+            self.open -= 1;
+        }
+        Ok(())
+    }
+}
+
+/// Split the input into CSV files that all keep the header row, returns the files with their row counts
+pub fn process_split(input: &str, opts: &SplitOptions) -> Result<Vec<(PathBuf, usize)>> {
+    let (mut reader, dialect) = sniffed_csv_reader(input, &opts.dialect)?;
+    let headers = csv_headers(&mut reader)?;
+    let column = match &opts.by {
+        SplitBy::Rows(0) | SplitBy::Bytes(0) => bail!("Chunk size must be greater than 0"),
+        SplitBy::Column(name) => Some(
+            headers
+                .iter()
+                .position(|h| h == name)
+                .with_context(|| format!("Column {:?} not found", name))?,
+        ),
+        _ => None,
+    };
+    let template = opts.template.clone().unwrap_or_else(|| match column {
+        Some(_) => "{stem}_{value}.csv".to_string(),
+        None => "{stem}_{index}.csv".to_string(),
+    });
+    let stem = match input {
+        "-" => "stdin".to_string(),
+        _ => Path::new(input)
+            .file_stem()
+            .map_or("output".to_string(), |s| s.to_string_lossy().to_string()),
+    };
+    fs::create_dir_all(&opts.output_dir)?;
+
+    // 探测出没有表头时，分块文件也不写表头
+    let mut splitter = Splitter {
+        opts,
+        template,
+        stem,
+        header: reader
+            .has_headers()
+            .then(|| ByteRecord::from(headers.clone())),
+        dialect,
+        created: HashSet::new(),
+        done: Vec::new(),
+    };
+    let mut current: Option<Chunk> = None;
+    let mut partitions = Partitions::default();
+    let mut record = ByteRecord::new();
+    while reader.read_byte_record(&mut record)? {
+        let size = record_size(&record);
+        let chunk = match (&opts.by, column) {
+            (_, Some(i)) => partitions.get(record.get(i).unwrap_or_default(), &mut splitter)?,
+            (SplitBy::Rows(rows), _) => {
+                if current.as_ref().is_some_and(|c| c.rows >= *rows) {
+                    splitter.close(current.take().unwrap())?;
+                }
+                splitter.current(&mut current)?
+            }
+            (SplitBy::Bytes(bytes), _) => {
+                // 超出大小时换新文件，单条记录超出上限也要写入
+                if current
+                    .as_ref()
+                    .is_some_and(|c| c.rows > 0 && c.bytes + size > *bytes)
+                {
+                    splitter.close(current.take().unwrap())?;
+                }
+                splitter.current(&mut current)?
+            }
+            _ => unreachable!(),
+        };
+        if let Some(writer) = &mut chunk.writer {
+            writer.write_byte_record(&record)?;
+        }
+        chunk.rows += 1;
+        chunk.bytes += size;
+    }
+
+    if let Some(chunk) = current {
+        splitter.close(chunk)?;
+    }
+    // 分区文件按值首次出现的顺序输出
+    for chunk in partitions.chunks {
+        splitter.close(chunk)?;
+    }
+    Ok(splitter.done)
+}
+
+struct Splitter<'a> {
+    opts: &'a SplitOptions,
+    template: String,
+    stem: String,
+    header: Option<ByteRecord>,
+    /// Dialect of the input after sniffing, the chunks are written in it
+    dialect: CsvDialect,
+    created: HashSet<PathBuf>,
+    done: Vec<(PathBuf, usize)>,
+}
+
+impl Splitter<'_> {
+    fn current<'c>(&mut self, current: &'c mut Option<Chunk>) -> Result<&'c mut Chunk> {
+        if current.is_none() {
+            let index = self.done.len() + 1;
+            *current = Some(self.create(index, "")?);
+        }
+        Ok(current.as_mut().unwrap())
+    }
+
+    fn create(&mut self, index: usize, value: &str) -> Result<Chunk> {
+        let name = self
+            .template
+            .replace("{stem}", &self.stem)
+            .replace("{index}", &index.to_string())
+            .replace("{value}", value);
+        let path = Path::new(&self.opts.output_dir).join(name);
+        // 模板缺少占位符或不同的值清理后同名时，不能覆盖已写的文件
+        if !self.created.insert(path.clone()) {
+            bail!(
+                "File name {:?} is used twice, add {{index}} to the template",
+                path
+            );
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = File::create(&path).with_context(|| format!("Failed to create {:?}", path))?;
+        let mut writer = self
+            .dialect
+            .writer_builder()
+            .from_writer(BufWriter::new(file));
+        let mut bytes = 0;
+        if let Some(header) = &self.header {
+            writer.write_byte_record(header)?;
+            bytes = record_size(header);
+        }
+        Ok(Chunk {
+            path,
+            writer: Some(writer),
+            rows: 0,
+            bytes,
+            used: 0,
+        })
+    }
+
+    /// Reopen a partition closed to free a file handle
+    fn append(&self, path: &Path) -> Result<Writer<BufWriter<File>>> {
+        let file = OpenOptions::new()
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to reopen {:?}", path))?;
+        Ok(self
+            .dialect
+            .writer_builder()
+            .from_writer(BufWriter::new(file)))
+    }
+
+    fn close(&mut self, chunk: Chunk) -> Result<()> {
+        if let Some(writer) = chunk.writer {
+            writer.into_inner()?;
+        }
+        self.done.push((chunk.path, chunk.rows));
+        Ok(())
+    }
+}
+
+/// Approximate size of the record as a CSV line, quoting is not counted
+fn record_size(record: &ByteRecord) -> u64 {
+    (record.as_slice().len() + record.len()) as u64
+}
+
+/// Make a column value usable as a file name
+fn sanitize(value: &str) -> String {
+    let name: String = value
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    match name.trim_matches('.') {
+        "" => "empty".to_string(),
+        _ => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(
+        name: &str,
+        by: SplitBy,
+        template: Option<&str>,
+    ) -> Result<(PathBuf, Vec<(PathBuf, usize)>)> {
+        let dir = std::env::temp_dir().join(format!("rcli-split-{}-{}", std::process::id(), name));
+        let opts = SplitOptions {
+            by,
+            dialect: CsvDialect::default(),
+            template: template.map(str::to_string),
+            output_dir: dir.to_string_lossy().to_string(),
+        };
+        let files = process_split("assets/juventus.csv", &opts)?;
+        Ok((dir, files))
+    }
+
+    #[test]
+    fn test_split_rows() -> Result<()> {
+        let (dir, files) = split("rows", SplitBy::Rows(10), None)?;
+        let rows: Vec<usize> = files.iter().map(|(_, rows)| *rows).collect();
+        assert_eq!(rows, [10, 10, 7]);
+        assert_eq!(files[2].0, dir.join("juventus_3.csv"));
+
+        let content = fs::read_to_string(&files[1].0)?;
+        assert!(content.starts_with("Name,Position,DOB,Nationality,Kit Number\n"));
+        assert_eq!(content.lines().count(), 11);
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_bytes() -> Result<()> {
+        let (dir, files) = split("bytes", SplitBy::Bytes(600), Some("part-{index}.csv"))?;
+        assert!(files.len() > 1);
+        for (path, _) in &files {
+            // 允许最后一条记录略微超出
+            assert!(fs::metadata(path)?.len() < 700);
+        }
+        assert_eq!(files.iter().map(|(_, rows)| rows).sum::<usize>(), 27);
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_by_column() -> Result<()> {
+        let (dir, files) = split("column", SplitBy::Column("Nationality".to_string()), None)?;
+        let italy = dir.join("juventus_Italy.csv");
+        let (_, rows) = files.iter().find(|(path, _)| *path == italy).unwrap();
+        let content = fs::read_to_string(&italy)?;
+        assert_eq!(content.lines().count(), rows + 1);
+        assert!(content.lines().skip(1).all(|line| line.contains(",Italy,")));
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_sniffed_headerless() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("rcli-split-{}-sniff", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let input = dir.join("data.csv");
+        fs::write(&input, "1;alpha;2.5\n2;beta;3.5\n3;gamma;4.5\n")?;
+        let opts = SplitOptions {
+            by: SplitBy::Rows(2),
+            dialect: CsvDialect {
+                sniff: Some(10),
+                ..Default::default()
+            },
+            template: None,
+            output_dir: dir.to_string_lossy().to_string(),
+        };
+        let files = process_split(&input.to_string_lossy(), &opts)?;
+        // 没有伪造的 column_N 表头，分隔符与输入相同
+        assert_eq!(
+            fs::read_to_string(&files[0].0)?,
+            "1;alpha;2.5\n2;beta;3.5\n"
+        );
+        assert_eq!(fs::read_to_string(&files[1].0)?, "3;gamma;4.5\n");
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
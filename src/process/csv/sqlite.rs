@@ -0,0 +1,146 @@
+use crate::cli::csv::ColumnType;
+use anyhow::Result;
+use rusqlite::types::{Value as SqlValue, ValueRef};
+use rusqlite::{params_from_iter, Connection};
+use serde_json::{Map, Value};
+use std::path::Path;
+
+/// Table name derived from the input file name, e.g. `assets/juventus.csv` -> `juventus`
+pub fn table_name(input: &str) -> String {
+    if input == "-" {
+        return "stdin".to_string();
+    }
+    let stem = Path::new(input)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let name: String = stem
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("t_{}", name)
+    } else {
+        name
+    }
+}
+
+pub fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn sql_type(ty: &ColumnType) -> &'static str {
+    match ty {
+        ColumnType::Integer => "INTEGER",
+        ColumnType::Float => "REAL",
+        ColumnType::Boolean => "BOOLEAN",
+        ColumnType::String | ColumnType::Null | ColumnType::Date(_) => "TEXT",
+    }
+}
+
+pub fn create_table(
+    conn: &Connection,
+    table: &str,
+    headers: &[String],
+    types: &[ColumnType],
+) -> Result<()> {
+    let columns = headers
+        .iter()
+        .zip(types)
+        .map(|(name, ty)| format!("{} {}", quote_ident(name), sql_type(ty)))
+        .collect::<Vec<_>>()
+        .join(", ");
+    conn.execute(
+        &format!("CREATE TABLE {} ({})", quote_ident(table), columns),
+        [],
+    )?;
+    Ok(())
+}
+
+/// Insert the records with one prepared statement. Run it inside a transaction, the caller
+/// decides when to commit so a failing record leaves nothing behind.
+pub fn insert_records(
+    conn: &Connection,
+    table: &str,
+    headers: &[String],
+    records: impl Iterator<Item = Result<Value>>,
+) -> Result<usize> {
+    let sql = format!(
+        "INSERT INTO {} ({}) VALUES ({})",
+        quote_ident(table),
+        headers
+            .iter()
+            .map(|h| quote_ident(h))
+            .collect::<Vec<_>>()
+            .join(", "),
+        vec!["?"; headers.len()].join(", ")
+    );
+
+    let mut stmt = conn.prepare(&sql)?;
+    let mut count = 0;
+    for record in records {
+        let record = record?;
+        stmt.execute(params_from_iter(
+            headers.iter().map(|h| to_sql(&record[h.as_str()])),
+        ))?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+fn to_sql(value: &Value) -> SqlValue {
+    match value {
+        Value::Null => SqlValue::Null,
+        Value::Bool(b) => SqlValue::Integer(*b as i64),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => SqlValue::Integer(i),
+            None => SqlValue::Real(n.as_f64().unwrap_or_default()),
+        },
+        Value::String(s) => SqlValue::Text(s.clone()),
+        _ => SqlValue::Text(value.to_string()),
+    }
+}
+
+/// Run a query and pass every result row to `f` as a JSON object
+pub fn query_records(
+    conn: &Connection,
+    sql: &str,
+    mut f: impl FnMut(Value) -> Result<()>,
+) -> Result<()> {
+    let mut stmt = conn.prepare(sql)?;
+    let columns = stmt
+        .columns()
+        .iter()
+        .map(|c| (c.name().to_string(), c.decl_type() == Some("BOOLEAN")))
+        .collect::<Vec<_>>();
+
+    // 连接查询时列名可能重复，为重复的列名加上序号
+    let mut names: Vec<String> = Vec::with_capacity(columns.len());
+    for (name, _) in &columns {
+        let mut unique = name.clone();
+        let mut n = 1;
+        while names.contains(&unique) {
+            n += 1;
+            unique = format!("{}_{}", name, n);
+        }
+        names.push(unique);
+    }
+
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let mut record = Map::with_capacity(names.len());
+        for (i, (name, (_, boolean))) in names.iter().zip(&columns).enumerate() {
+            let value = match row.get_ref(i)? {
+                ValueRef::Null => Value::Null,
+                ValueRef::Integer(n) if *boolean => Value::Bool(n != 0),
+                ValueRef::Integer(n) => Value::from(n),
+                ValueRef::Real(n) => Value::from(n),
+                ValueRef::Text(s) => Value::String(String::from_utf8_lossy(s).to_string()),
+                ValueRef::Blob(b) => Value::String(String::from_utf8_lossy(b).to_string()),
+            };
+            record.insert(name.clone(), value);
+        }
+        f(Value::Object(record))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,567 @@
+use crate::cli::csv::{ColumnType, ColumnTypes, ReportFormat};
+use crate::process::csv::dialect::CsvDialect;
+use crate::process::csv::expr::compare;
+use crate::process::csv::reader::TypedReader;
+use crate::process::csv::show::render_table;
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeSet, HashMap};
+use std::hash::{Hash, Hasher};
+
+/// Precision of the HyperLogLog sketch, 2^14 registers give about 0.8% standard error
+const HLL_PRECISION: u32 = 14;
+/// Minimum number of counters kept for approximate top-k
+const TOP_CAPACITY: usize = 1000;
+/// Numbers kept for an exact median before switching to the P² estimator
+const MEDIAN_LIMIT: usize = 100_000;
+
+pub struct StatsOptions {
+    pub dialect: CsvDialect,
+    pub types: ColumnTypes,
+    pub top: usize,
+    /// Distinct values counted exactly before switching to HyperLogLog
+    pub distinct_limit: usize,
+    pub format: ReportFormat,
+    pub width: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ColumnReport {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: String,
+    pub count: usize,
+    pub nulls: usize,
+    pub distinct: usize,
+    pub distinct_approx: bool,
+    pub min: Value,
+    pub max: Value,
+    pub mean: Option<f64>,
+    pub median: Option<f64>,
+    pub median_approx: bool,
+    pub stddev: Option<f64>,
+    pub top: Vec<TopValue>,
+    pub longest: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TopValue {
+    pub value: String,
+    pub count: usize,
+}
+
+pub fn process_stats(input: &str, opts: &StatsOptions) -> Result<()> {
+    let reports = column_stats(input, opts)?;
+    match opts.format {
+        ReportFormat::Json => println!("{}", serde_json::to_string_pretty(&reports)?),
+        ReportFormat::Table => {
+            let headers = [
+                "Column", "Type", "Count", "Nulls", "Distinct", "Min", "Max", "Mean", "Median",
+                "Stddev", "Longest", "Top",
+            ]
+            .map(String::from);
+            let rows = reports.iter().map(report_row).collect::<Vec<_>>();
+            print!("{}", render_table(&headers, &rows, opts.width));
+        }
+    }
+    Ok(())
+}
+
+pub fn column_stats(input: &str, opts: &StatsOptions) -> Result<Vec<ColumnReport>> {
+    let reader = TypedReader::open(input, &opts.dialect, true, &opts.types)?;
+    let mut columns = reader
+        .headers()
+        .iter()
+        .zip(reader.types())
+        .map(|(name, ty)| ColumnStats::new(name.clone(), ty.clone(), opts))
+        .collect::<Vec<_>>();
+
+    for record in reader.records() {
+        let record = record?;
+        for column in columns.iter_mut() {
+            column.update(&record[column.name.as_str()]);
+        }
+    }
+
+    Ok(columns.into_iter().map(|c| c.report(opts.top)).collect())
+}
+
+struct ColumnStats {
+    name: String,
+    ty: ColumnType,
+    count: usize,
+    nulls: usize,
+    distinct: Distinct,
+    min: Value,
+    max: Value,
+    moments: Moments,
+    median: Median,
+    longest: usize,
+}
+
+/// Exact value counts while they fit in the limit, then a HyperLogLog sketch plus Space-Saving top-k
+enum Distinct {
+    Exact {
+        counts: HashMap<String, usize>,
+        limit: usize,
+        top: usize,
+    },
+    Approx {
+        sketch: HyperLogLog,
+        top: SpaceSaving,
+    },
+}
+
+impl ColumnStats {
+    fn new(name: String, ty: ColumnType, opts: &StatsOptions) -> Self {
+        Self {
+            name,
+            ty,
+            count: 0,
+            nulls: 0,
+            distinct: Distinct::Exact {
+                counts: HashMap::new(),
+                limit: opts.distinct_limit,
+                top: opts.top,
+            },
+            min: Value::Null,
+            max: Value::Null,
+            moments: Moments::default(),
+            median: Median::Exact(Vec::new()),
+            longest: 0,
+        }
+    }
+
+    fn update(&mut self, value: &Value) {
+        self.count += 1;
+        let text = match value {
+            Value::Null => None,
+            Value::String(s) if s.trim().is_empty() => None,
+            Value::String(s) => Some(s.clone()),
+            _ => Some(value.to_string()),
+        };
+        let Some(text) = text else {
+            self.nulls += 1;
+            return;
+        };
+
+        self.longest = self.longest.max(text.chars().count());
+        if let Some(n) = value.as_f64() {
+            self.moments.insert(n);
+            self.median.insert(n);
+        }
+        if self.min.is_null() || compare(value, &self.min).is_some_and(|o| o.is_lt()) {
+            self.min = value.clone();
+        }
+        if self.max.is_null() || compare(value, &self.max).is_some_and(|o| o.is_gt()) {
+            self.max = value.clone();
+        }
+
+        match &mut self.distinct {
+            Distinct::Exact { counts, limit, top } => {
+                *counts.entry(text).or_default() += 1;
+                if counts.len() > *limit {
+                    // 不同值太多时切换为近似统计，内存占用固定
+                    let mut sketch = HyperLogLog::new(HLL_PRECISION);
+                    for value in counts.keys() {
+                        sketch.insert(value);
+                    }
+                    let mut counts = counts.drain().collect::<Vec<_>>();
+                    counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+                    let capacity = (*top * 100).max(TOP_CAPACITY);
+                    let mut top = SpaceSaving::new(capacity);
+                    for (value, count) in counts.into_iter().take(capacity) {
+                        top.insert_n(value, count);
+                    }
+                    self.distinct = Distinct::Approx { sketch, top };
+                }
+            }
+            Distinct::Approx { sketch, top } => {
+                sketch.insert(&text);
+                top.insert_n(text, 1);
+            }
+        }
+    }
+
+    fn report(mut self, k: usize) -> ColumnReport {
+        let (distinct, distinct_approx, top) = match self.distinct {
+            Distinct::Exact { counts, .. } => (counts.len(), false, top_k(counts.into_iter(), k)),
+            Distinct::Approx { sketch, top } => (sketch.estimate(), true, top.top_k(k)),
+        };
+
+        let numeric = matches!(self.ty, ColumnType::Integer | ColumnType::Float);
+        let (mean, median, stddev) = match numeric && self.moments.n > 0 {
+            true => (
+                Some(self.moments.mean),
+                self.median.estimate(),
+                self.moments.stddev(),
+            ),
+            false => (None, None, None),
+        };
+
+        ColumnReport {
+            name: self.name,
+            ty: self.ty.to_string(),
+            count: self.count,
+            nulls: self.nulls,
+            distinct,
+            distinct_approx,
+            min: self.min,
+            max: self.max,
+            mean,
+            median,
+            median_approx: matches!(self.median, Median::Approx(_)),
+            stddev,
+            top,
+            longest: self.longest,
+        }
+    }
+}
+
+fn top_k(counts: impl Iterator<Item = (String, usize)>, k: usize) -> Vec<TopValue> {
+    let mut counts = counts.collect::<Vec<_>>();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts
+        .into_iter()
+        .take(k)
+        .map(|(value, count)| TopValue { value, count })
+        .collect()
+}
+
+fn report_row(report: &ColumnReport) -> Vec<String> {
+    let number = |n: Option<f64>| n.map(|n| format!("{:.2}", n)).unwrap_or_default();
+    let value = |v: &Value| match v {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        _ => v.to_string(),
+    };
+    let distinct = if report.distinct_approx {
+        format!("~{}", report.distinct)
+    } else {
+        report.distinct.to_string()
+    };
+    let top = report
+        .top
+        .iter()
+        .map(|t| format!("{} ({})", t.value, t.count))
+        .collect::<Vec<_>>()
+        .join(", ");
+    vec![
+        report.name.clone(),
+        report.ty.clone(),
+        report.count.to_string(),
+        report.nulls.to_string(),
+        distinct,
+        value(&report.min),
+        value(&report.max),
+        number(report.mean),
+        match report.median_approx {
+            true => format!("~{}", number(report.median)),
+            false => number(report.median),
+        },
+        number(report.stddev),
+        report.longest.to_string(),
+        top,
+    ]
+}
+
+/// HyperLogLog cardinality sketch
+struct HyperLogLog {
+    precision: u32,
+    registers: Vec<u8>,
+}
+
+impl HyperLogLog {
+    fn new(precision: u32) -> Self {
+        Self {
+            precision,
+            registers: vec![0; 1 << precision],
+        }
+    }
+
+    fn insert(&mut self, value: &str) {
+        // DefaultHasher::new() 使用固定的密钥，相同的值总是得到相同的哈希
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        let index = (hash >> (64 - self.precision)) as usize;
+        let rest = hash << self.precision;
+        let rank = (rest.leading_zeros() + 1).min(64 - self.precision + 1) as u8;
+        self.registers[index] = self.registers[index].max(rank);
+    }
+
+    fn estimate(&self) -> usize {
+        let m = self.registers.len() as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self.registers.iter().map(|&r| 2f64.powi(-(r as i32))).sum();
+        let estimate = alpha * m * m / sum;
+
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        if estimate <= 2.5 * m && zeros > 0 {
+            // 基数较小时使用线性计数修正
+            (m * (m / zeros as f64).ln()).round() as usize
+        } else {
+            estimate.round() as usize
+        }
+    }
+}
+
+/// Space-Saving heavy hitters, keeps at most `capacity` counters
+struct SpaceSaving {
+    capacity: usize,
+    counts: HashMap<String, usize>,
+    /// The same counters ordered by count, the smallest one is evicted in O(log capacity)
+    order: BTreeSet<(usize, String)>,
+}
+
+impl SpaceSaving {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            counts: HashMap::with_capacity(capacity + 1),
+            order: BTreeSet::new(),
+        }
+    }
+
+    fn insert_n(&mut self, value: String, n: usize) {
+        if let Some(count) = self.counts.get_mut(&value) {
+            self.order.remove(&(*count, value.clone()));
+            *count += n;
+            self.order.insert((*count, value));
+            return;
+        }
+        let mut count = n;
+        if self.counts.len() >= self.capacity {
+            // 替换计数最小的值，新值继承其计数
+            if let Some((min_count, min_value)) = self.order.pop_first() {
+                self.counts.remove(&min_value);
+                count += min_count;
+            }
+        }
+        self.counts.insert(value.clone(), count);
+        self.order.insert((count, value));
+    }
+
+    fn top_k(self, k: usize) -> Vec<TopValue> {
+        top_k(self.counts.into_iter(), k)
+    }
+}
+
+/// Running mean and variance with Welford's algorithm
+#[derive(Debug, Default)]
+struct Moments {
+    n: usize,
+    mean: f64,
+    /// Sum of squared differences from the mean
+    m2: f64,
+}
+
+impl Moments {
+    fn insert(&mut self, x: f64) {
+        self.n += 1;
+        let delta = x - self.mean;
+        self.mean += delta / self.n as f64;
+        self.m2 += delta * (x - self.mean);
+    }
+
+    /// Sample standard deviation
+    fn stddev(&self) -> Option<f64> {
+        (self.n > 1).then(|| (self.m2 / (self.n - 1) as f64).sqrt())
+    }
+}
+
+/// Exact median while the numbers fit in `MEDIAN_LIMIT`, then a P² estimate in constant memory
+enum Median {
+    Exact(Vec<f64>),
+    Approx(P2Median),
+}
+
+impl Median {
+    fn insert(&mut self, x: f64) {
+        match self {
+            Median::Exact(numbers) => {
+                numbers.push(x);
+                if numbers.len() > MEDIAN_LIMIT {
+                    numbers.sort_by(f64::total_cmp);
+                    *self = Median::Approx(P2Median::new(numbers));
+                }
+            }
+            Median::Approx(p2) => p2.insert(x),
+        }
+    }
+
+    fn estimate(&mut self) -> Option<f64> {
+        match self {
+            Median::Exact(numbers) if numbers.is_empty() => None,
+            Median::Exact(numbers) => {
+                numbers.sort_by(f64::total_cmp);
+                let mid = numbers.len() / 2;
+                match numbers.len().is_multiple_of(2) {
+                    true => Some((numbers[mid - 1] + numbers[mid]) / 2.0),
+                    false => Some(numbers[mid]),
+                }
+            }
+            Median::Approx(p2) => Some(p2.heights[2]),
+        }
+    }
+}
+
+/// P² median estimator (Jain & Chlamtac), five markers track the minimum, the quartiles,
+/// the median and the maximum
+struct P2Median {
+    heights: [f64; 5],
+    positions: [f64; 5],
+    desired: [f64; 5],
+}
+
+impl P2Median {
+    const INCREMENTS: [f64; 5] = [0.0, 0.25, 0.5, 0.75, 1.0];
+
+    /// Start from a sorted sample of at least five numbers
+    fn new(sorted: &[f64]) -> Self {
+        let last = (sorted.len() - 1) as f64;
+        let desired = Self::INCREMENTS.map(|q| 1.0 + last * q);
+        let positions = desired.map(f64::round);
+        let heights = positions.map(|p| sorted[p as usize - 1]);
+        Self {
+            heights,
+            positions,
+            desired,
+        }
+    }
+
+    fn insert(&mut self, x: f64) {
+        let q = &mut self.heights;
+        let k = if x < q[0] {
+            q[0] = x;
+            0
+        } else if x >= q[4] {
+            q[4] = x;
+            3
+        } else {
+            (0..4).find(|&i| x < q[i + 1]).unwrap_or(3)
+        };
+        for n in &mut self.positions[k + 1..] {
+            *n += 1.0;
+        }
+        for (desired, inc) in self.desired.iter_mut().zip(Self::INCREMENTS) {
+            *desired += inc;
+        }
+
+        // 中间三个标记偏离期望位置时用抛物线插值调整高度，越界时退回线性插值
+        for i in 1..4 {
+            let n = self.positions;
+            let d = self.desired[i] - n[i];
+            if (d >= 1.0 && n[i + 1] - n[i] > 1.0) || (d <= -1.0 && n[i - 1] - n[i] < -1.0) {
+                let d = d.signum();
+                let q = &mut self.heights;
+                let parabolic = q[i]
+                    + d / (n[i + 1] - n[i - 1])
+                        * ((n[i] - n[i - 1] + d) * (q[i + 1] - q[i]) / (n[i + 1] - n[i])
+                            + (n[i + 1] - n[i] - d) * (q[i] - q[i - 1]) / (n[i] - n[i - 1]));
+                q[i] = if q[i - 1] < parabolic && parabolic < q[i + 1] {
+                    parabolic
+                } else {
+                    let j = if d > 0.0 { i + 1 } else { i - 1 };
+                    q[i] + d * (q[j] - q[i]) / (n[j] - n[i])
+                };
+                self.positions[i] += d;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opts(distinct_limit: usize) -> StatsOptions {
+        StatsOptions {
+            dialect: CsvDialect::default(),
+            types: ColumnTypes::default(),
+            top: 2,
+            distinct_limit,
+            format: ReportFormat::Json,
+            width: 120,
+        }
+    }
+
+    #[test]
+    fn test_column_stats() -> Result<()> {
+        let reports = column_stats("assets/juventus.csv", &opts(1000))?;
+        let kit = reports.iter().find(|r| r.name == "Kit Number").unwrap();
+        assert_eq!(kit.ty, "int");
+        assert_eq!(kit.count, 27);
+        assert_eq!(kit.nulls, 0);
+        assert_eq!(kit.min, Value::from(1));
+        assert_eq!(kit.max, Value::from(77));
+        assert!(kit.mean.is_some() && kit.stddev.is_some());
+
+        let nationality = reports.iter().find(|r| r.name == "Nationality").unwrap();
+        assert!(!nationality.distinct_approx);
+        assert_eq!(nationality.top[0].value, "Italy");
+        assert_eq!(nationality.top[0].count, 8);
+        Ok(())
+    }
+
+    #[test]
+    fn test_switch_to_approx_distinct() -> Result<()> {
+        let reports = column_stats("assets/juventus.csv", &opts(5))?;
+        let name = reports.iter().find(|r| r.name == "Name").unwrap();
+        assert!(name.distinct_approx);
+        assert!(name.distinct.abs_diff(27) <= 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_hyperloglog_estimate() {
+        let mut sketch = HyperLogLog::new(HLL_PRECISION);
+        for i in 0..100_000 {
+            sketch.insert(&i.to_string());
+        }
+        let estimate = sketch.estimate() as f64;
+        assert!((estimate - 100_000.0).abs() / 100_000.0 < 0.03);
+    }
+
+    #[test]
+    fn test_moments_and_median() {
+        let mut moments = Moments::default();
+        let mut median = Median::Exact(Vec::new());
+        // 乱序插入 1..=200001，超过上限后切换为 P² 估计
+        let n = 2 * MEDIAN_LIMIT as u64 + 1;
+        for i in 0..n {
+            let x = (i * 7919 % n + 1) as f64;
+            moments.insert(x);
+            median.insert(x);
+        }
+        let expected = (n + 1) as f64 / 2.0;
+        assert!((moments.mean - expected).abs() < 1e-6);
+        let stddev = ((n * (n + 1)) as f64 / 12.0).sqrt();
+        assert!((moments.stddev().unwrap() - stddev).abs() / stddev < 1e-9);
+        assert!(matches!(median, Median::Approx(_)));
+        let estimate = median.estimate().unwrap();
+        assert!(
+            (estimate - expected).abs() / expected < 0.01,
+            "{}",
+            estimate
+        );
+
+        let mut median = Median::Exact(vec![3.0, 1.0, 4.0, 2.0]);
+        assert_eq!(median.estimate(), Some(2.5));
+    }
+
+    #[test]
+    fn test_space_saving() {
+        let mut top = SpaceSaving::new(3);
+        for value in ["a", "b", "a", "c", "a", "d", "b"] {
+            top.insert_n(value.to_string(), 1);
+        }
+        assert_eq!(top.counts.len(), 3);
+        assert_eq!(top.order.len(), 3);
+        let ret = top.top_k(1);
+        assert_eq!((ret[0].value.as_str(), ret[0].count), ("a", 3));
+    }
+}
@@ -0,0 +1,357 @@
+use crate::cli::csv::{ColumnType, ColumnTypes};
+use crate::process::csv::expr::Expr;
+use crate::process::csv::nested::cell;
+use anyhow::{anyhow, bail, Result};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use regex::Regex;
+use serde_json::{Map, Value};
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Default)]
+pub struct TransformOptions {
+    /// `old=new` pairs
+    pub rename: Vec<String>,
+    /// `column=input format->output format`, the output defaults to `%Y-%m-%d`
+    pub date_format: Vec<String>,
+    /// Columns to trim, upper- or lowercase, `*` for all columns
+    pub trim: Vec<String>,
+    pub upper: Vec<String>,
+    pub lower: Vec<String>,
+    /// `column=pattern->replacement`
+    pub replace: Vec<String>,
+    /// `name = expression`
+    pub compute: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Case {
+    Upper,
+    Lower,
+}
+
+/// A change to the value of one column
+enum Step {
+    Trim,
+    Case(Case),
+    Replace(Regex, String),
+    Date { input: String, output: String },
+}
+
+/// Renames columns, rewrites values and appends computed columns.
+/// Value transforms run in the order trim, case, replace, date format,
+/// columns are referred to by their new or their original name.
+pub struct Transformer {
+    /// Output name of every input column
+    names: Vec<(String, String)>,
+    steps: Vec<(String, Step)>,
+    computed: Vec<(String, Expr)>,
+    headers: Vec<String>,
+}
+
+impl Transformer {
+    pub fn new(headers: &[String], opts: &TransformOptions) -> Result<Self> {
+        let mut names: Vec<(String, String)> =
+            headers.iter().map(|h| (h.clone(), h.clone())).collect();
+        for pair in &opts.rename {
+            let (old, new) = split_spec(pair, "=", "old=new")?;
+            let Some(i) = names.iter().position(|(o, _)| o == old) else {
+                bail!("Column {:?} not found", old);
+            };
+            names[i].1 = new.to_string();
+        }
+        let mut output: Vec<String> = names.iter().map(|(_, n)| n.clone()).collect();
+        if let Some(name) = duplicate(&output) {
+            bail!("Duplicate column {:?} after renaming", name);
+        }
+
+        let resolve = |name: &str| -> Result<Vec<String>> {
+            let name = name.trim();
+            if name == "*" {
+                return Ok(output.clone());
+            }
+            names
+                .iter()
+                .find(|(old, new)| new == name || old == name)
+                .map(|(_, new)| vec![new.clone()])
+                .ok_or_else(|| anyhow!("Column {:?} not found", name))
+        };
+
+        let mut steps = Vec::new();
+        for column in &opts.trim {
+            steps.extend(resolve(column)?.into_iter().map(|c| (c, Step::Trim)));
+        }
+        for (columns, case) in [(&opts.upper, Case::Upper), (&opts.lower, Case::Lower)] {
+            for column in columns {
+                steps.extend(resolve(column)?.into_iter().map(|c| (c, Step::Case(case))));
+            }
+        }
+        for spec in &opts.replace {
+            let usage = "column=pattern->replacement";
+            let (column, rest) = split_spec(spec, "=", usage)?;
+            let (pattern, replacement) = rest
+                .split_once("->")
+                .ok_or_else(|| anyhow!("Invalid {:?}, expected {}", spec, usage))?;
+            let regex = Regex::new(pattern)?;
+            for column in resolve(column)? {
+                steps.push((
+                    column,
+                    Step::Replace(regex.clone(), replacement.to_string()),
+                ));
+            }
+        }
+        for spec in &opts.date_format {
+            let (column, formats) = split_spec(spec, "=", "column=input->output")?;
+            let (input, output) = formats.split_once("->").unwrap_or((formats, "%Y-%m-%d"));
+            for format in [input, output] {
+                if StrftimeItems::new(format).any(|item| item == Item::Error) {
+                    bail!("Invalid date format {:?}", format);
+                }
+            }
+            for column in resolve(column)? {
+                let step = Step::Date {
+                    input: input.to_string(),
+                    output: output.to_string(),
+                };
+                steps.push((column, step));
+            }
+        }
+        // 按 trim、大小写、替换、日期的顺序执行
+        steps.sort_by_key(|(_, step)| match step {
+            Step::Trim => 0,
+            Step::Case(_) => 1,
+            Step::Replace(..) => 2,
+            Step::Date { .. } => 3,
+        });
+
+        let mut computed = Vec::with_capacity(opts.compute.len());
+        for spec in &opts.compute {
+            let (name, source) = split_spec(spec, "=", "name = expression")?;
+            let expr = Expr::parse(source)?;
+            // 计算列可以引用前面的计算列
+            for column in expr.columns() {
+                if !output.iter().any(|c| c == column) {
+                    bail!("Column {:?} not found in {:?}", column, spec);
+                }
+            }
+            if !output.iter().any(|c| c == name) {
+                output.push(name.to_string());
+            }
+            computed.push((name.to_string(), expr));
+        }
+
+        Ok(Self {
+            names,
+            steps,
+            computed,
+            headers: output,
+        })
+    }
+
+    /// Headers after renaming, computed columns come last
+    pub fn headers(&self) -> &[String] {
+        &self.headers
+    }
+
+    /// Types of the input columns under their new names, columns whose values are rewritten
+    /// by a replacement or date format are left out
+    pub fn column_types(&self, types: &[ColumnType]) -> ColumnTypes {
+        let rewritten = |name: &str| {
+            self.steps.iter().any(|(column, step)| {
+                column == name && matches!(step, Step::Replace(..) | Step::Date { .. })
+            })
+        };
+        ColumnTypes(
+            self.names
+                .iter()
+                .zip(types)
+                .filter(|((_, new), _)| !rewritten(new))
+                .map(|((_, new), ty)| (new.clone(), ty.clone()))
+                .collect(),
+        )
+    }
+
+    pub fn apply(&self, record: Value) -> Result<Value> {
+        let Value::Object(record) = record else {
+            return Ok(record);
+        };
+        let mut object: Map<String, Value> = record
+            .into_iter()
+            .map(|(key, value)| {
+                let key = match self.names.iter().find(|(old, _)| *old == key) {
+                    Some((_, new)) => new.clone(),
+                    None => key,
+                };
+                (key, value)
+            })
+            .collect();
+
+        for (column, step) in &self.steps {
+            let Some(value) = object.get_mut(column) else {
+                continue;
+            };
+            if value.is_null() {
+                continue;
+            }
+            *value = match step {
+                Step::Trim => match &*value {
+                    Value::String(s) => Value::String(s.trim().to_string()),
+                    _ => continue,
+                },
+                Step::Case(case) => match &*value {
+                    Value::String(s) if *case == Case::Upper => Value::String(s.to_uppercase()),
+                    Value::String(s) => Value::String(s.to_lowercase()),
+                    _ => continue,
+                },
+                Step::Replace(regex, replacement) => {
+                    Value::String(regex.replace_all(&cell(value), replacement).to_string())
+                }
+                Step::Date { input, output } => {
+                    let text = cell(value);
+                    if text.trim().is_empty() {
+                        continue;
+                    }
+                    Value::String(reformat_date(text.trim(), input, output).ok_or_else(|| {
+                        anyhow!(
+                            "Column {:?}: {:?} does not match the date format {:?}",
+                            column,
+                            text,
+                            input
+                        )
+                    })?)
+                }
+            };
+        }
+
+        for (name, expr) in &self.computed {
+            let value = expr.eval(&object);
+            object.insert(name.clone(), value);
+        }
+        Ok(Value::Object(object))
+    }
+}
+
+/// Parse with the input format, trailing text is ignored, and print with the output format
+fn reformat_date(value: &str, input: &str, output: &str) -> Option<String> {
+    let datetime = match NaiveDateTime::parse_and_remainder(value, input) {
+        Ok((datetime, _)) => datetime,
+        Err(_) => NaiveDate::parse_and_remainder(value, input)
+            .ok()?
+            .0
+            .and_time(NaiveTime::MIN),
+    };
+    let mut text = String::new();
+    write!(text, "{}", datetime.format(output)).ok()?;
+    Some(text)
+}
+
+/// Split `name=rest` specs, the name is trimmed
+fn split_spec<'a>(spec: &'a str, separator: &str, usage: &str) -> Result<(&'a str, &'a str)> {
+    match spec.split_once(separator) {
+        Some((name, rest)) if !name.trim().is_empty() => Ok((name.trim(), rest)),
+        _ => Err(anyhow!("Invalid {:?}, expected {}", spec, usage)),
+    }
+}
+
+fn duplicate(names: &[String]) -> Option<&String> {
+    names
+        .iter()
+        .enumerate()
+        .find(|(i, name)| names[..*i].contains(name))
+        .map(|(_, name)| name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::csv::dialect::CsvDialect;
+    use crate::process::csv::reader::TypedReader;
+    use serde_json::json;
+
+    fn transform(opts: &TransformOptions) -> Result<Vec<Value>> {
+        let reader = TypedReader::open(
+            "assets/juventus.csv",
+            &CsvDialect::default(),
+            true,
+            &ColumnTypes::default(),
+        )?;
+        let transformer = Transformer::new(reader.headers(), opts)?;
+        reader
+            .records()
+            .map(|record| transformer.apply(record?))
+            .collect()
+    }
+
+    #[test]
+    fn test_transform_juventus() -> Result<()> {
+        let opts = TransformOptions {
+            rename: vec!["Kit Number=kit".into(), "DOB=born".into()],
+            date_format: vec!["DOB=%b %d, %Y->%Y-%m-%d".into()],
+            upper: vec!["Nationality".into()],
+            replace: vec![r"Position=^(\w)\w+$->$1".into()],
+            compute: vec![
+                "age = years_since(born)".into(),
+                "label = concat(Name, ' #', kit)".into(),
+            ],
+            ..Default::default()
+        };
+        let records = transform(&opts)?;
+        let buffon = &records[2];
+        assert_eq!(buffon["born"], json!("1978-01-28"));
+        assert_eq!(buffon["kit"], json!(77));
+        assert_eq!(buffon["Nationality"], json!("ITALY"));
+        assert_eq!(buffon["Position"], json!("G"));
+        assert_eq!(buffon["label"], json!("Gianluigi Buffon #77"));
+        assert!(buffon["age"].as_u64().unwrap() >= 46);
+
+        let keys: Vec<_> = buffon.as_object().unwrap().keys().collect();
+        assert_eq!(
+            keys,
+            [
+                "Name",
+                "Position",
+                "born",
+                "Nationality",
+                "kit",
+                "age",
+                "label"
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_transforms() {
+        let headers = vec!["Name".to_string(), "DOB".to_string()];
+        let invalid = |opts: TransformOptions| Transformer::new(&headers, &opts).is_err();
+        assert!(invalid(TransformOptions {
+            rename: vec!["Club=team".into()],
+            ..Default::default()
+        }));
+        assert!(invalid(TransformOptions {
+            rename: vec!["DOB=Name".into()],
+            ..Default::default()
+        }));
+        assert!(invalid(TransformOptions {
+            date_format: vec!["DOB=%Q".into()],
+            ..Default::default()
+        }));
+        assert!(invalid(TransformOptions {
+            compute: vec!["age = years_since(Birthday)".into()],
+            ..Default::default()
+        }));
+    }
+
+    #[test]
+    fn test_reformat_date() {
+        assert_eq!(
+            reformat_date("Apr 18, 1990 (29)", "%b %d, %Y", "%d/%m/%Y"),
+            Some("18/04/1990".to_string())
+        );
+        assert_eq!(
+            reformat_date("2024-05-01 10:30", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S"),
+            Some("2024-05-01T10:30:00".to_string())
+        );
+        assert_eq!(reformat_date("yesterday", "%Y-%m-%d", "%Y"), None);
+    }
+}
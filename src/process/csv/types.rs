@@ -0,0 +1,277 @@
+use crate::cli::csv::{ColumnType, ColumnTypes};
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use csv::StringRecord;
+use serde_json::{Map, Number, Value};
+
+#[derive(Debug, Clone, Copy)]
+struct Candidates {
+    seen: bool,
+    integer: bool,
+    float: bool,
+    boolean: bool,
+    date: bool,
+}
+
+impl Default for Candidates {
+    fn default() -> Self {
+        Self {
+            seen: false,
+            integer: true,
+            float: true,
+            boolean: true,
+            date: true,
+        }
+    }
+}
+
+impl Candidates {
+    fn update(&mut self, value: &str) {
+        let value = value.trim();
+        if value.is_empty() {
+            return;
+        }
+        self.seen = true;
+        self.integer = self.integer && is_integer(value);
+        self.float = self.float && is_float(value);
+        self.boolean = self.boolean && parse_bool(value).is_some();
+        self.date = self.date && is_date(value);
+    }
+
+    fn column_type(&self) -> ColumnType {
+        if !self.seen {
+            ColumnType::Null
+        } else if self.integer {
+            ColumnType::Integer
+        } else if self.float {
+            ColumnType::Float
+        } else if self.boolean {
+            ColumnType::Boolean
+        } else if self.date {
+            ColumnType::Date(None)
+        } else {
+            ColumnType::String
+        }
+    }
+}
+
+/// Narrow down the type of every column while scanning the records
+#[derive(Debug, Default)]
+pub struct TypeInference {
+    columns: Vec<Candidates>,
+}
+
+impl TypeInference {
+    pub fn update(&mut self, record: &StringRecord) {
+        if self.columns.len() < record.len() {
+            self.columns.resize(record.len(), Candidates::default());
+        }
+        for (candidates, value) in self.columns.iter_mut().zip(record.iter()) {
+            candidates.update(value);
+        }
+    }
+
+    /// Combine the candidates of another part of the same input
+    pub fn merge(&mut self, other: TypeInference) {
+        if self.columns.len() < other.columns.len() {
+            self.columns
+                .resize(other.columns.len(), Candidates::default());
+        }
+        for (candidates, other) in self.columns.iter_mut().zip(other.columns) {
+            candidates.seen |= other.seen;
+            candidates.integer &= other.integer;
+            candidates.float &= other.float;
+            candidates.boolean &= other.boolean;
+            candidates.date &= other.date;
+        }
+    }
+
+    /// Inferred types for the headers, explicit overrides take precedence
+    pub fn types(&self, headers: &[String], overrides: &ColumnTypes) -> Vec<ColumnType> {
+        headers
+            .iter()
+            .enumerate()
+            .map(|(i, name)| match overrides.get(name) {
+                Some(ty) => ty.clone(),
+                None => self
+                    .columns
+                    .get(i)
+                    .map_or(ColumnType::Null, Candidates::column_type),
+            })
+            .collect()
+    }
+}
+
+/// Types used when inference is disabled, only the overrides are applied
+pub fn declared_types(headers: &[String], overrides: &ColumnTypes) -> Vec<ColumnType> {
+    headers
+        .iter()
+        .map(|name| overrides.get(name).cloned().unwrap_or(ColumnType::String))
+        .collect()
+}
+
+/// Turns raw records into JSON objects with typed values
+pub struct RecordConverter {
+    headers: Vec<String>,
+    types: Vec<ColumnType>,
+    /// Columns whose type was given explicitly, a value that does not match is an error
+    strict: Vec<bool>,
+}
+
+impl RecordConverter {
+    pub fn new(headers: Vec<String>, types: Vec<ColumnType>, overrides: &ColumnTypes) -> Self {
+        let strict = headers.iter().map(|h| overrides.get(h).is_some()).collect();
+        Self {
+            headers,
+            types,
+            strict,
+        }
+    }
+
+    pub fn headers(&self) -> &[String] {
+        &self.headers
+    }
+
+    pub fn types(&self) -> &[ColumnType] {
+        &self.types
+    }
+
+    pub fn convert(&self, record: &StringRecord) -> Result<Value> {
+        // headers.iter() -> 获得headers迭代器
+        // record.iter() -> 获得record迭代器
+        // zip -> 将多个迭代器合并成一个元组迭代器 [(header, type, value), ...]
+        let mut object = Map::with_capacity(self.headers.len());
+        for (((header, ty), strict), value) in self
+            .headers
+            .iter()
+            .zip(self.types.iter())
+            .zip(self.strict.iter())
+            .zip(record.iter())
+        {
+            let value = match parse_value(value, ty) {
+                Ok(value) => value,
+                // 推断类型只基于采样时，后续不匹配的值保留为字符串
+                Err(_) if !strict => Value::String(value.to_string()),
+                Err(e) => {
+                    let line = record.position().map_or(0, |p| p.line());
+                    return Err(e).with_context(|| format!("Line {}, column {:?}", line, header));
+                }
+            };
+            object.insert(header.clone(), value);
+        }
+        Ok(Value::Object(object))
+    }
+}
+
+/// Convert a raw cell into a JSON value of the given type, empty cells become null
+pub fn parse_value(value: &str, ty: &ColumnType) -> Result<Value> {
+    let trimmed = value.trim();
+    if trimmed.is_empty() && *ty != ColumnType::String {
+        return Ok(Value::Null);
+    }
+
+    let invalid = || anyhow!("Invalid {} value {:?}", ty, value);
+    let value = match ty {
+        ColumnType::String => Value::String(value.to_string()),
+        ColumnType::Integer => Value::from(trimmed.parse::<i64>().map_err(|_| invalid())?),
+        ColumnType::Float => trimmed
+            .parse::<f64>()
+            .ok()
+            .and_then(Number::from_f64)
+            .map(Value::Number)
+            .ok_or_else(invalid)?,
+        ColumnType::Boolean => Value::Bool(parse_bool(trimmed).ok_or_else(invalid)?),
+        ColumnType::Null => Value::Null,
+        ColumnType::Date(None) => {
+            if !is_date(trimmed) {
+                return Err(invalid());
+            }
+            Value::String(trimmed.to_string())
+        }
+        ColumnType::Date(Some(format)) => {
+            Value::String(parse_date(trimmed, format).ok_or_else(invalid)?)
+        }
+    };
+    Ok(value)
+}
+
+fn is_integer(value: &str) -> bool {
+    !has_leading_zero(value) && value.parse::<i64>().is_ok()
+}
+
+fn is_float(value: &str) -> bool {
+    !has_leading_zero(value)
+        && value.bytes().any(|b| b.is_ascii_digit())
+        && value.parse::<f64>().is_ok_and(f64::is_finite)
+}
+
+/// 以0开头的数字（如编号、邮编）保留为字符串
+fn has_leading_zero(value: &str) -> bool {
+    let digits = value.strip_prefix(['-', '+']).unwrap_or(value).as_bytes();
+    digits.len() > 1 && digits[0] == b'0' && digits[1].is_ascii_digit()
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_lowercase().as_str() {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None,
+    }
+}
+
+fn is_date(value: &str) -> bool {
+    DateTime::parse_from_rfc3339(value).is_ok()
+        || NaiveDate::parse_from_str(value, "%Y-%m-%d").is_ok()
+}
+
+/// Parse with a chrono format and normalize to ISO 8601, trailing text after the date is ignored
+fn parse_date(value: &str, format: &str) -> Option<String> {
+    if let Ok((datetime, _)) = NaiveDateTime::parse_and_remainder(value, format) {
+        return Some(datetime.format("%Y-%m-%dT%H:%M:%S").to_string());
+    }
+    NaiveDate::parse_and_remainder(value, format)
+        .ok()
+        .map(|(date, _)| date.format("%Y-%m-%d").to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn infer(values: &[&str]) -> ColumnType {
+        let mut inference = TypeInference::default();
+        for value in values {
+            inference.update(&StringRecord::from(vec![*value]));
+        }
+        inference.types(&["a".into()], &ColumnTypes::default())[0].clone()
+    }
+
+    #[test]
+    fn test_infer_types() {
+        assert_eq!(infer(&["1", "37", "", "-5"]), ColumnType::Integer);
+        assert_eq!(infer(&["1", "2.5"]), ColumnType::Float);
+        assert_eq!(infer(&["true", "FALSE"]), ColumnType::Boolean);
+        assert_eq!(infer(&["", " "]), ColumnType::Null);
+        assert_eq!(
+            infer(&["2024-11-24", "2024-11-24T10:00:00Z"]),
+            ColumnType::Date(None)
+        );
+        assert_eq!(infer(&["007", "12"]), ColumnType::String);
+        assert_eq!(infer(&["NaN"]), ColumnType::String);
+    }
+
+    #[test]
+    fn test_parse_value() -> Result<()> {
+        assert_eq!(parse_value("10", &ColumnType::Integer)?, Value::from(10));
+        assert_eq!(parse_value("", &ColumnType::Integer)?, Value::Null);
+        assert_eq!(parse_value("1.5", &ColumnType::Float)?, Value::from(1.5));
+        assert!(parse_value("ten", &ColumnType::Integer).is_err());
+
+        let ty = ColumnType::Date(Some("%b %d, %Y".into()));
+        assert_eq!(
+            parse_value("Apr 18, 1990 (29)", &ty)?,
+            Value::from("1990-04-18")
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,388 @@
+use crate::cli::csv::ReportFormat;
+use crate::process::csv::dialect::{csv_headers, csv_reader, CsvDialect};
+use crate::process::csv::expr::compare;
+use crate::process::csv::schema::{ColumnSchema, Schema};
+use crate::process::csv::types::parse_value;
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use serde::Serialize;
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+pub struct ValidateOptions {
+    pub dialect: CsvDialect,
+    pub format: ReportFormat,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ValidationReport {
+    pub valid: bool,
+    pub rows: usize,
+    pub violations: Vec<Violation>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Violation {
+    pub line: u64,
+    pub column: String,
+    pub value: Option<String>,
+    pub reason: String,
+}
+
+/// Validate the input against the schema, any violation makes the command fail
+pub fn process_validate(input: &str, schema: &str, opts: &ValidateOptions) -> Result<()> {
+    let schema = Schema::load(schema)?;
+    let report = validate(input, &schema, &opts.dialect)?;
+    match opts.format {
+        ReportFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+        ReportFormat::Table => {
+            for v in &report.violations {
+                match &v.value {
+                    None if v.column.is_empty() => println!("Line {}: {}", v.line, v.reason),
+                    Some(value) => println!(
+                        "Line {}, column {:?}: {} (value {:?})",
+                        v.line, v.column, v.reason, value
+                    ),
+                    None => println!("Line {}, column {:?}: {}", v.line, v.column, v.reason),
+                }
+            }
+            println!(
+                "{} rows checked, {} violations",
+                report.rows,
+                report.violations.len()
+            );
+        }
+    }
+
+    if !report.valid {
+        bail!(
+            "{} violations found in {:?}",
+            report.violations.len(),
+            input
+        );
+    }
+    Ok(())
+}
+
+pub fn validate(input: &str, schema: &Schema, dialect: &CsvDialect) -> Result<ValidationReport> {
+    let mut reader = csv_reader(input, dialect)?;
+    let headers = csv_headers(&mut reader)?;
+    let mut violations = Vec::new();
+
+    // 探测方言时可能判定没有表头，以读取器为准
+    let header_line = if reader.has_headers() { 1 } else { 0 };
+    let mut columns = Vec::with_capacity(schema.columns.len());
+    for column in &schema.columns {
+        match headers.iter().position(|h| *h == column.name) {
+            Some(index) => columns.push(ColumnValidator::new(column, index)?),
+            None => violations.push(Violation {
+                line: header_line,
+                column: column.name.clone(),
+                value: None,
+                reason: "column is missing".to_string(),
+            }),
+        }
+    }
+    if schema.strict {
+        for header in &headers {
+            if !schema.columns.iter().any(|c| c.name == *header) {
+                violations.push(Violation {
+                    line: header_line,
+                    column: header.clone(),
+                    value: None,
+                    reason: "column is not declared in the schema".to_string(),
+                });
+            }
+        }
+    }
+
+    let mut rows = 0;
+    for record in reader.records() {
+        // 格式错误的行记为违规并继续校验，只有 IO 错误才中止
+        let record = match record {
+            Ok(record) => record,
+            Err(e) if e.is_io_error() => return Err(e.into()),
+            Err(e) => {
+                rows += 1;
+                violations.push(Violation {
+                    line: e.position().map_or(0, |p| p.line()),
+                    column: String::new(),
+                    value: None,
+                    reason: malformed_reason(&e),
+                });
+                continue;
+            }
+        };
+        let line = record.position().map_or(0, |p| p.line());
+        rows += 1;
+        for column in columns.iter_mut() {
+            let value = record.get(column.index).unwrap_or_default();
+            if let Err(reason) = column.check(value, line) {
+                violations.push(Violation {
+                    line,
+                    column: column.schema.name.clone(),
+                    value: Some(value.to_string()),
+                    reason,
+                });
+            }
+        }
+    }
+
+    Ok(ValidationReport {
+        valid: violations.is_empty(),
+        rows,
+        violations,
+    })
+}
+
+fn malformed_reason(e: &csv::Error) -> String {
+    match e.kind() {
+        csv::ErrorKind::UnequalLengths {
+            expected_len, len, ..
+        } => format!(
+            "malformed row, expected {} fields, found {}",
+            expected_len, len
+        ),
+        csv::ErrorKind::Utf8 { err, .. } => format!(
+            "malformed row, field {} is not valid UTF-8",
+            err.field() + 1
+        ),
+        _ => format!("malformed row, {}", e),
+    }
+}
+
+struct ColumnValidator<'a> {
+    schema: &'a ColumnSchema,
+    index: usize,
+    pattern: Option<Regex>,
+    /// Values seen so far and the line they first appeared on
+    seen: HashMap<String, u64>,
+}
+
+impl<'a> ColumnValidator<'a> {
+    fn new(schema: &'a ColumnSchema, index: usize) -> Result<Self> {
+        // 正则需要匹配整个值
+        let pattern = schema
+            .pattern
+            .as_ref()
+            .map(|p| Regex::new(&format!("^(?:{})$", p)))
+            .transpose()
+            .with_context(|| format!("Invalid pattern for column {:?}", schema.name))?;
+        Ok(Self {
+            schema,
+            index,
+            pattern,
+            seen: HashMap::new(),
+        })
+    }
+
+    fn check(&mut self, value: &str, line: u64) -> Result<(), String> {
+        let schema = self.schema;
+        if value.trim().is_empty() {
+            return if schema.required {
+                Err("required value is missing".to_string())
+            } else {
+                Ok(())
+            };
+        }
+
+        let parsed = parse_value(value, &schema.ty)
+            .map_err(|_| format!("expected a value of type {}", schema.ty))?;
+        if let Some(pattern) = &self.pattern {
+            if !pattern.is_match(value) {
+                return Err(format!("does not match pattern {:?}", pattern.as_str()));
+            }
+        }
+        if let Some(values) = &schema.values {
+            if !values.iter().any(|v| v == value) {
+                return Err(format!("is not one of {}", values.join(", ")));
+            }
+        }
+        if let Some(min) = &schema.min {
+            if out_of_range(&parsed, min, Ordering::Less) {
+                return Err(format!("is less than the minimum {}", min));
+            }
+        }
+        if let Some(max) = &schema.max {
+            if out_of_range(&parsed, max, Ordering::Greater) {
+                return Err(format!("is greater than the maximum {}", max));
+            }
+        }
+        if schema.unique {
+            if let Some(first) = self.seen.get(value) {
+                return Err(format!("duplicate value, first seen on line {}", first));
+            }
+            self.seen.insert(value.to_string(), line);
+        }
+        Ok(())
+    }
+}
+
+fn out_of_range(value: &Value, bound: &Value, outside: Ordering) -> bool {
+    compare(value, bound) == Some(outside)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema(yaml: &str) -> Schema {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_validate_juventus() -> Result<()> {
+        let schema = schema(
+            r#"
+columns:
+  - name: Name
+    required: true
+    unique: true
+  - name: Position
+    enum: [Goalkeeper, Centre-Back, Left-Back, Right-Back, Defensive Midfield,
+           Central Midfield, Right Winger, Left Winger, Second Striker, Centre-Forward]
+  - name: DOB
+    pattern: '[A-Z][a-z]{2} \d{1,2}, \d{4} \(\d+\)'
+  - name: Kit Number
+    type: int
+    unique: true
+    min: 1
+    max: 99
+"#,
+        );
+        let report = validate("assets/juventus.csv", &schema, &CsvDialect::default())?;
+        assert_eq!(report.rows, 27);
+        assert!(report.valid, "{:?}", report.violations);
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_reports_every_violation() -> Result<()> {
+        let schema = schema(
+            r#"
+strict: true
+columns:
+  - name: Name
+    required: true
+  - name: Nationality
+    enum: [Italy]
+  - name: Kit Number
+    type: int
+    max: 30
+  - name: Salary
+"#,
+        );
+        let report = validate("assets/juventus.csv", &schema, &CsvDialect::default())?;
+        assert!(!report.valid);
+
+        let reasons = |column: &str| {
+            report
+                .violations
+                .iter()
+                .filter(|v| v.column == column)
+                .count()
+        };
+        assert_eq!(reasons("Salary"), 1);
+        assert_eq!(reasons("Position"), 1);
+        assert_eq!(reasons("DOB"), 1);
+        assert_eq!(reasons("Nationality"), 27 - 8);
+        assert_eq!(
+            report.violations.iter().find(|v| v.column == "Kit Number"),
+            Some(&Violation {
+                line: 3,
+                column: "Kit Number".to_string(),
+                value: Some("37".to_string()),
+                reason: "is greater than the maximum 30".to_string(),
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_malformed_rows() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("rcli-ragged-{}.csv", std::process::id()));
+        std::fs::write(&path, "id,name\n1,a\n2\nx,b\n4,c,extra\n")?;
+        let schema = schema(
+            r#"
+columns:
+  - name: id
+    type: int
+"#,
+        );
+        let report = validate(&path.to_string_lossy(), &schema, &CsvDialect::default())?;
+        std::fs::remove_file(path)?;
+
+        assert_eq!(report.rows, 4);
+        let found: Vec<_> = report
+            .violations
+            .iter()
+            .map(|v| (v.line, v.reason.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (3, "malformed row, expected 2 fields, found 1"),
+                (4, "expected a value of type int"),
+                (5, "malformed row, expected 2 fields, found 3"),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_sniffed_headerless() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("rcli-headerless-{}.csv", std::process::id()));
+        std::fs::write(&path, "1;alpha;2.5\n2;beta;3.5\n3;gamma;4.5\n")?;
+        let schema = schema(
+            r#"
+columns:
+  - name: id
+    type: int
+"#,
+        );
+        let dialect = CsvDialect {
+            sniff: Some(10),
+            ..Default::default()
+        };
+        let report = validate(&path.to_string_lossy(), &schema, &dialect)?;
+        std::fs::remove_file(path)?;
+
+        // 第1行是数据，缺失的列不应记在第1行
+        assert_eq!(report.rows, 3);
+        assert_eq!(report.violations.len(), 1);
+        assert_eq!(report.violations[0].line, 0);
+        assert_eq!(report.violations[0].reason, "column is missing");
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_value() -> Result<()> {
+        let schema = schema(
+            r#"
+columns:
+  - name: id
+    type: int
+    required: true
+    unique: true
+    pattern: '\d{3}'
+"#,
+        );
+        let mut column = ColumnValidator::new(&schema.columns[0], 0)?;
+        assert!(column.check("101", 2).is_ok());
+        assert_eq!(
+            column.check("", 3),
+            Err("required value is missing".to_string())
+        );
+        assert_eq!(
+            column.check("abc", 4),
+            Err("expected a value of type int".to_string())
+        );
+        assert!(column.check("1000", 5).is_err());
+        assert_eq!(
+            column.check("101", 6),
+            Err("duplicate value, first seen on line 2".to_string())
+        );
+        Ok(())
+    }
+}
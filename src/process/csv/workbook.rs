@@ -0,0 +1,250 @@
+use crate::process::csv::dialect::CsvDialect;
+use crate::process::csv::nested::cell;
+use crate::process::csv::writer::RecordWriter;
+use anyhow::{anyhow, bail, Context, Result};
+use calamine::{open_workbook_auto, Data, Range, Reader};
+use rust_xlsxwriter::{Format, Workbook, Worksheet};
+use serde_json::Value;
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
+
+const WORKBOOK_EXTENSIONS: [&str; 5] = ["xlsx", "xlsm", "xlsb", "xls", "ods"];
+/// Columns of a worksheet, A to XFD
+const MAX_COLUMNS: u32 = 16_384;
+
+/// Whether the input is a spreadsheet rather than a CSV file
+pub fn is_workbook(input: &str) -> bool {
+    Path::new(input)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .is_some_and(|e| WORKBOOK_EXTENSIONS.contains(&e.as_str()))
+}
+
+/// Read a sheet of a workbook as CSV text in the given dialect, so every command can use it
+pub fn workbook_reader(input: &str, dialect: &CsvDialect) -> Result<Box<dyn Read>> {
+    let mut workbook =
+        open_workbook_auto(input).with_context(|| format!("Failed to open {:?}", input))?;
+    let names = workbook.sheet_names();
+    let name = match &dialect.sheet {
+        None => names
+            .first()
+            .ok_or_else(|| anyhow!("{:?} has no sheets", input))?,
+        Some(sheet) => match names.iter().find(|n| *n == sheet) {
+            Some(name) => name,
+            // 找不到同名工作表时按序号（从0开始）选择
+            None => sheet
+                .parse::<usize>()
+                .ok()
+                .and_then(|i| names.get(i))
+                .ok_or_else(|| anyhow!("Sheet {:?} not found, available: {:?}", sheet, names))?,
+        },
+    }
+    .clone();
+
+    let mut range = workbook.worksheet_range(&name)?;
+    if let Some(cells) = &dialect.range {
+        let (start, end) = parse_range(cells)?;
+        range = range.range(start, end);
+    }
+
+    let mut writer = dialect
+        .writer_builder()
+        .flexible(true)
+        .from_writer(Vec::new());
+    for row in range_rows(&range) {
+        writer.write_record(row)?;
+    }
+    Ok(Box::new(Cursor::new(writer.into_inner()?)))
+}
+
+fn range_rows(range: &Range<Data>) -> impl Iterator<Item = Vec<String>> + '_ {
+    range.rows().map(|row| row.iter().map(data_text).collect())
+}
+
+fn data_text(data: &Data) -> String {
+    match data {
+        Data::Empty | Data::Error(_) => String::new(),
+        Data::String(s) | Data::DateTimeIso(s) | Data::DurationIso(s) => s.clone(),
+        Data::Int(n) => n.to_string(),
+        // Excel中的整数以浮点数保存
+        Data::Float(n) if n.fract() == 0.0 && n.abs() < 1e15 => (*n as i64).to_string(),
+        Data::Float(n) => n.to_string(),
+        Data::Bool(b) => b.to_string(),
+        Data::DateTime(dt) => match dt.as_datetime() {
+            Some(dt) if dt.time() == chrono::NaiveTime::MIN => dt.format("%Y-%m-%d").to_string(),
+            Some(dt) => dt.format("%Y-%m-%d %H:%M:%S").to_string(),
+            None => dt.as_f64().to_string(),
+        },
+    }
+}
+
+/// Parse a cell range like `B2:D20` into zero-based (row, column) corners
+fn parse_range(range: &str) -> Result<((u32, u32), (u32, u32))> {
+    let (start, end) = range
+        .split_once(':')
+        .ok_or_else(|| anyhow!("Invalid cell range {:?}, expected e.g. A1:D10", range))?;
+    let (start, end) = (parse_cell(start)?, parse_cell(end)?);
+    if start.0 > end.0 || start.1 > end.1 {
+        bail!("Invalid cell range {:?}", range);
+    }
+    Ok((start, end))
+}
+
+fn parse_cell(cell: &str) -> Result<(u32, u32)> {
+    let cell = cell.trim().to_uppercase();
+    let split = cell
+        .find(|c: char| c.is_ascii_digit())
+        .ok_or_else(|| anyhow!("Invalid cell {:?}", cell))?;
+    let (letters, digits) = cell.split_at(split);
+    if letters.is_empty() || !letters.chars().all(|c| c.is_ascii_uppercase()) {
+        bail!("Invalid cell {:?}", cell);
+    }
+    let column = letters
+        .bytes()
+        .try_fold(0u32, |acc, b| {
+            acc.checked_mul(26)?.checked_add((b - b'A' + 1) as u32)
+        })
+        .filter(|&column| column <= MAX_COLUMNS)
+        .ok_or_else(|| anyhow!("Invalid cell {:?}, the last column is XFD", cell))?;
+    let row: u32 = digits
+        .parse()
+        .map_err(|_| anyhow!("Invalid cell {:?}", cell))?;
+    if row == 0 {
+        bail!("Invalid cell {:?}", cell);
+    }
+    Ok((row - 1, column - 1))
+}
+
+/// Writes records to a single worksheet with a bold, frozen header row and fitted columns
+pub struct XlsxWriter {
+    output: Box<dyn Write>,
+    worksheet: Worksheet,
+    headers: Vec<String>,
+    row: u32,
+}
+
+impl XlsxWriter {
+    pub fn new(output: Box<dyn Write>) -> Self {
+        Self {
+            output,
+            worksheet: Worksheet::new(),
+            headers: Vec::new(),
+            row: 0,
+        }
+    }
+
+    fn column(&mut self, key: &str) -> Result<u16> {
+        if let Some(i) = self.headers.iter().position(|h| h == key) {
+            return Ok(i as u16);
+        }
+        // 新出现的字段追加为新列
+        let col = u16::try_from(self.headers.len())?;
+        let bold = Format::new().set_bold();
+        self.worksheet
+            .write_string_with_format(0, col, key, &bold)?;
+        self.headers.push(key.to_string());
+        Ok(col)
+    }
+}
+
+impl RecordWriter for XlsxWriter {
+    fn write(&mut self, record: &Value) -> Result<()> {
+        self.row += 1;
+        let row = self.row;
+        let fields = match record {
+            Value::Object(object) => object.iter().collect::<Vec<_>>(),
+            _ => vec![],
+        };
+        if fields.is_empty() {
+            let col = self.column("value")?;
+            self.worksheet.write_string(row, col, cell(record))?;
+            return Ok(());
+        }
+        for (key, value) in fields {
+            let col = self.column(key)?;
+            match value {
+                Value::Null => {}
+                Value::Bool(b) => {
+                    self.worksheet.write_boolean(row, col, *b)?;
+                }
+                Value::Number(n) => {
+                    self.worksheet
+                        .write_number(row, col, n.as_f64().unwrap_or_default())?;
+                }
+                _ => {
+                    self.worksheet.write_string(row, col, cell(value))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        let mut worksheet = std::mem::replace(&mut self.worksheet, Worksheet::new());
+        worksheet.set_freeze_panes(1, 0)?;
+        worksheet.autofit();
+        let mut workbook = Workbook::new();
+        workbook.push_worksheet(worksheet);
+        self.output.write_all(&workbook.save_to_buffer()?)?;
+        self.output.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::csv::dialect::csv_reader;
+    use crate::process::csv::writer::tests::SharedBuf;
+    use serde_json::json;
+    use std::fs;
+
+    #[test]
+    fn test_parse_range() -> Result<()> {
+        assert_eq!(parse_range("A1:D10")?, ((0, 0), (9, 3)));
+        assert_eq!(parse_range("b2:AA3")?, ((1, 1), (2, 26)));
+        assert!(parse_range("A0:B2").is_err());
+        assert!(parse_range("D1:A1").is_err());
+        assert_eq!(parse_range("A1:XFD2")?, ((0, 0), (1, 16_383)));
+        assert!(parse_range("A1:XFE2").is_err());
+        assert!(parse_range("AAAAAAAAA1:B2").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_xlsx_round_trip() -> Result<()> {
+        let buf = SharedBuf::default();
+        let mut writer = XlsxWriter::new(Box::new(buf.clone()));
+        writer.write(&json!({"Name": "Buffon", "Kit": 77, "Keeper": true}))?;
+        writer.write(&json!({"Name": "Perin, Mattia", "Kit": 37.5, "Club": "Juventus"}))?;
+        writer.finish()?;
+
+        let path = std::env::temp_dir().join(format!("rcli-{}.xlsx", std::process::id()));
+        fs::write(&path, buf.0.borrow().as_slice())?;
+        let input = path.to_string_lossy().to_string();
+        assert!(is_workbook(&input));
+
+        let mut content = String::new();
+        csv_reader(&input, &CsvDialect::default())?
+            .into_inner()
+            .read_to_string(&mut content)?;
+        assert_eq!(
+            content,
+            "Name,Kit,Keeper,Club\nBuffon,77,true,\n\"Perin, Mattia\",37.5,,Juventus\n"
+        );
+
+        let dialect = CsvDialect {
+            sheet: Some("0".to_string()),
+            range: Some("A1:B2".to_string()),
+            ..Default::default()
+        };
+        let mut content = String::new();
+        csv_reader(&input, &dialect)?
+            .into_inner()
+            .read_to_string(&mut content)?;
+        assert_eq!(content, "Name,Kit\nBuffon,77\n");
+
+        fs::remove_file(path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,513 @@
+use crate::cli::csv::{ColumnTypes, Compression, OutputFormat};
+use crate::process::csv::columnar::ColumnarWriter;
+use crate::process::csv::nested::cell;
+use crate::process::csv::workbook::XlsxWriter;
+use anyhow::Result;
+use serde_json::{Map, Value};
+use std::io::Write;
+use unicode_width::UnicodeWidthStr;
+
+pub trait RecordWriter {
+    /// Write a single record to the output
+    fn write(&mut self, record: &Value) -> Result<()>;
+
+    /// Close the document and flush the output
+    fn finish(&mut self) -> Result<()>;
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct WriterOptions {
+    /// Stylesheet embedded into HTML output
+    pub css: Option<String>,
+    /// Column types of the input, used for the schema of columnar formats
+    pub columns: ColumnTypes,
+    /// Compression of columnar formats, each format has its own default
+    pub compression: Option<Compression>,
+}
+
+pub fn record_writer(
+    format: OutputFormat,
+    output: Box<dyn Write>,
+    opts: &WriterOptions,
+) -> Box<dyn RecordWriter> {
+    match format {
+        OutputFormat::Json => Box::new(JsonWriter::new(output)),
+        OutputFormat::Ndjson => Box::new(NdjsonWriter { output }),
+        OutputFormat::Yaml => Box::new(YamlWriter::new(output)),
+        OutputFormat::Toml => Box::new(TomlWriter::new(output)),
+        OutputFormat::Csv => Box::new(CsvWriter::new(output)),
+        OutputFormat::Xlsx => Box::new(XlsxWriter::new(output)),
+        OutputFormat::Markdown => Box::new(TableWriter::new(output, render_markdown)),
+        OutputFormat::Html => {
+            let css = opts.css.clone();
+            Box::new(TableWriter::new(output, move |headers, rows| {
+                render_html(headers, rows, css.as_deref())
+            }))
+        }
+        OutputFormat::Parquet | OutputFormat::Arrow => Box::new(ColumnarWriter::new(
+            format,
+            output,
+            opts.columns.clone(),
+            opts.compression,
+        )),
+    }
+}
+
+/// Writes a JSON array element by element
+pub struct JsonWriter {
+    output: Box<dyn Write>,
+    count: usize,
+}
+
+impl JsonWriter {
+    pub fn new(output: Box<dyn Write>) -> Self {
+        Self { output, count: 0 }
+    }
+}
+
+impl RecordWriter for JsonWriter {
+    fn write(&mut self, record: &Value) -> Result<()> {
+        self.output
+            .write_all(if self.count == 0 { b"[" } else { b"," })?;
+        serde_json::to_writer(&mut self.output, record)?;
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if self.count == 0 {
+            self.output.write_all(b"[")?;
+        }
+        self.output.write_all(b"]")?;
+        self.output.flush()?;
+        Ok(())
+    }
+}
+
+/// Writes one JSON document per line
+pub struct NdjsonWriter {
+    output: Box<dyn Write>,
+}
+
+impl RecordWriter for NdjsonWriter {
+    fn write(&mut self, record: &Value) -> Result<()> {
+        serde_json::to_writer(&mut self.output, record)?;
+        self.output.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.output.flush()?;
+        Ok(())
+    }
+}
+
+/// Writes a YAML sequence, every record is emitted as a one-element sequence
+pub struct YamlWriter {
+    output: Box<dyn Write>,
+    count: usize,
+}
+
+impl YamlWriter {
+    pub fn new(output: Box<dyn Write>) -> Self {
+        Self { output, count: 0 }
+    }
+}
+
+impl RecordWriter for YamlWriter {
+    fn write(&mut self, record: &Value) -> Result<()> {
+        let item = serde_yaml::to_string(&[record])?;
+        self.output.write_all(item.as_bytes())?;
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if self.count == 0 {
+            self.output.write_all(b"[]\n")?;
+        }
+        self.output.flush()?;
+        Ok(())
+    }
+}
+
+/// Writes CSV rows, the header is taken from the first record
+pub struct CsvWriter {
+    writer: csv::Writer<Box<dyn Write>>,
+    headers: Option<Vec<String>>,
+}
+
+impl CsvWriter {
+    pub fn new(output: Box<dyn Write>) -> Self {
+        Self {
+            writer: csv::Writer::from_writer(output),
+            headers: None,
+        }
+    }
+}
+
+impl RecordWriter for CsvWriter {
+    fn write(&mut self, record: &Value) -> Result<()> {
+        let Value::Object(row) = record else {
+            self.writer.write_record([cell(record)])?;
+            return Ok(());
+        };
+        let headers = match &self.headers {
+            Some(headers) => headers,
+            None => {
+                self.writer.write_record(row.keys())?;
+                self.headers.insert(row.keys().cloned().collect())
+            }
+        };
+        self.writer.write_record(
+            headers
+                .iter()
+                .map(|h| row.get(h).map(cell).unwrap_or_default()),
+        )?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Writes every record as an element of the `records` array of tables
+pub struct TomlWriter {
+    output: Box<dyn Write>,
+    count: usize,
+}
+
+impl TomlWriter {
+    pub fn new(output: Box<dyn Write>) -> Self {
+        Self { output, count: 0 }
+    }
+}
+
+impl RecordWriter for TomlWriter {
+    fn write(&mut self, record: &Value) -> Result<()> {
+        // TOML没有null，空值直接省略
+        let mut document = toml::Table::new();
+        document.insert(
+            "records".to_string(),
+            toml::Value::Array(vec![toml::Value::try_from(without_nulls(record))?]),
+        );
+        if self.count > 0 {
+            self.output.write_all(b"\n")?;
+        }
+        self.output
+            .write_all(toml::to_string(&document)?.as_bytes())?;
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if self.count == 0 {
+            self.output.write_all(b"records = []\n")?;
+        }
+        self.output.flush()?;
+        Ok(())
+    }
+}
+
+fn without_nulls(value: &Value) -> Value {
+    match value {
+        Value::Object(object) => Value::Object(
+            object
+                .iter()
+                .filter(|(_, v)| !v.is_null())
+                .map(|(k, v)| (k.clone(), without_nulls(v)))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(
+            items
+                .iter()
+                .filter(|v| !v.is_null())
+                .map(without_nulls)
+                .collect(),
+        ),
+        _ => value.clone(),
+    }
+}
+
+type RenderTable = dyn Fn(&[String], &[Map<String, Value>]) -> String;
+
+/// Buffers the records and renders them as a table once the columns are known
+pub struct TableWriter {
+    output: Box<dyn Write>,
+    headers: Vec<String>,
+    rows: Vec<Map<String, Value>>,
+    render: Box<RenderTable>,
+}
+
+impl TableWriter {
+    pub fn new(
+        output: Box<dyn Write>,
+        render: impl Fn(&[String], &[Map<String, Value>]) -> String + 'static,
+    ) -> Self {
+        Self {
+            output,
+            headers: Vec::new(),
+            rows: Vec::new(),
+            render: Box::new(render),
+        }
+    }
+}
+
+impl RecordWriter for TableWriter {
+    fn write(&mut self, record: &Value) -> Result<()> {
+        let row = match record {
+            Value::Object(object) => object.clone(),
+            _ => Map::from_iter([("value".to_string(), record.clone())]),
+        };
+        // 列为所有记录字段的并集，按首次出现的顺序
+        for key in row.keys() {
+            if !self.headers.contains(key) {
+                self.headers.push(key.clone());
+            }
+        }
+        self.rows.push(row);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        let table = (self.render)(&self.headers, &self.rows);
+        self.output.write_all(table.as_bytes())?;
+        self.output.flush()?;
+        Ok(())
+    }
+}
+
+/// Numeric columns are right aligned
+fn numeric_columns(headers: &[String], rows: &[Map<String, Value>]) -> Vec<bool> {
+    headers
+        .iter()
+        .map(|h| {
+            let mut values = rows
+                .iter()
+                .filter_map(|row| row.get(h))
+                .filter(|v| !v.is_null());
+            values.clone().next().is_some() && values.all(Value::is_number)
+        })
+        .collect()
+}
+
+fn render_markdown(headers: &[String], rows: &[Map<String, Value>]) -> String {
+    let escape = |text: String| text.replace('|', "\\|").replace('\n', "<br>");
+    let header_cells: Vec<String> = headers.iter().map(|h| escape(h.clone())).collect();
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| {
+            headers
+                .iter()
+                .map(|h| escape(row.get(h).map(cell).unwrap_or_default()))
+                .collect()
+        })
+        .collect();
+    let numeric = numeric_columns(headers, rows);
+
+    let mut widths: Vec<usize> = header_cells.iter().map(|h| h.width().max(3)).collect();
+    for row in &cells {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.width());
+        }
+    }
+
+    let line = |cells: &[String]| {
+        let cells = cells
+            .iter()
+            .enumerate()
+            .map(|(i, cell)| {
+                let pad = " ".repeat(widths[i] - cell.width());
+                if numeric[i] {
+                    format!(" {}{} ", pad, cell)
+                } else {
+                    format!(" {}{} ", cell, pad)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("|");
+        format!("|{}|\n", cells)
+    };
+    let separator = widths
+        .iter()
+        .zip(&numeric)
+        .map(|(&w, &numeric)| {
+            if numeric {
+                format!(" {}: ", "-".repeat(w - 1))
+            } else {
+                format!(" :{} ", "-".repeat(w - 1))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("|");
+
+    let mut table = line(&header_cells);
+    table.push_str(&format!("|{}|\n", separator));
+    for row in &cells {
+        table.push_str(&line(row));
+    }
+    table
+}
+
+fn render_html(headers: &[String], rows: &[Map<String, Value>], css: Option<&str>) -> String {
+    let numeric = numeric_columns(headers, rows);
+    let mut html = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    if let Some(css) = css {
+        html.push_str(&format!("<style>\n{}\n</style>\n", css.trim_end()));
+    }
+    html.push_str("</head>\n<body>\n<table>\n<thead>\n<tr>");
+    for header in headers {
+        html.push_str(&format!("<th>{}</th>", escape_html(header)));
+    }
+    html.push_str("</tr>\n</thead>\n<tbody>\n");
+    for row in rows {
+        html.push_str("<tr>");
+        for (header, &numeric) in headers.iter().zip(&numeric) {
+            let text = escape_html(&row.get(header).map(cell).unwrap_or_default());
+            if numeric {
+                html.push_str(&format!("<td style=\"text-align: right\">{}</td>", text));
+            } else {
+                html.push_str(&format!("<td>{}</td>", text));
+            }
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</tbody>\n</table>\n</body>\n</html>\n");
+    html
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// An in-memory output that stays readable after being boxed into a writer
+    #[derive(Clone, Default)]
+    pub(crate) struct SharedBuf(pub(crate) Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn write_all(format: OutputFormat, records: &[Value]) -> Result<String> {
+        let buf = SharedBuf::default();
+        let mut writer = record_writer(format, Box::new(buf.clone()), &WriterOptions::default());
+        for record in records {
+            writer.write(record)?;
+        }
+        writer.finish()?;
+        let content = buf.0.borrow().clone();
+        Ok(String::from_utf8(content)?)
+    }
+
+    #[test]
+    fn test_streaming_writers_match_whole_document() -> Result<()> {
+        let records = vec![json!({"a": 1, "b": "x"}), json!({"a": 2, "b": null})];
+        for records in [&records[..], &[]] {
+            assert_eq!(
+                write_all(OutputFormat::Json, records)?,
+                serde_json::to_string(records)?
+            );
+            assert_eq!(
+                write_all(OutputFormat::Yaml, records)?,
+                serde_yaml::to_string(records)?
+            );
+        }
+        assert_eq!(
+            write_all(OutputFormat::Ndjson, &records)?,
+            "{\"a\":1,\"b\":\"x\"}\n{\"a\":2,\"b\":null}\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_csv_writer() -> Result<()> {
+        let records = vec![
+            json!({"name": "Buffon", "kit": 77, "birth": {"country": "Italy"}}),
+            json!({"name": "Perin, Mattia", "kit": null}),
+        ];
+        assert_eq!(
+            write_all(OutputFormat::Csv, &records)?,
+            "name,kit,birth\nBuffon,77,\"{\"\"country\"\":\"\"Italy\"\"}\"\n\"Perin, Mattia\",,\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_toml_writer() -> Result<()> {
+        let records = vec![
+            json!({"name": "Buffon", "kit": 77, "birth": {"country": "Italy"}}),
+            json!({"name": "Perin", "kit": null}),
+        ];
+        let content = write_all(OutputFormat::Toml, &records)?;
+        let document: toml::Table = toml::from_str(&content)?;
+        let records = document["records"].as_array().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["kit"].as_integer(), Some(77));
+        assert_eq!(records[0]["birth"]["country"].as_str(), Some("Italy"));
+        assert!(records[1].get("kit").is_none());
+        assert_eq!(write_all(OutputFormat::Toml, &[])?, "records = []\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_markdown_writer() -> Result<()> {
+        let records = vec![
+            json!({"Name": "Buffon", "Kit": 77}),
+            json!({"Name": "A|B", "Kit": 1, "Note": "x"}),
+        ];
+        assert_eq!(
+            write_all(OutputFormat::Markdown, &records)?,
+            "| Name   | Kit | Note |\n\
+             | :----- | --: | :--- |\n\
+             | Buffon |  77 |      |\n\
+             | A\\|B   |   1 | x    |\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_html_writer() -> Result<()> {
+        let buf = SharedBuf::default();
+        let opts = WriterOptions {
+            css: Some("td { padding: 4px; }".to_string()),
+            ..Default::default()
+        };
+        let mut writer = record_writer(OutputFormat::Html, Box::new(buf.clone()), &opts);
+        writer.write(&json!({"Name": "<b>Buffon</b>", "Kit": 77}))?;
+        writer.finish()?;
+        let html = String::from_utf8(buf.0.borrow().clone())?;
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<style>\ntd { padding: 4px; }\n</style>"));
+        assert!(html.contains("<tr><th>Name</th><th>Kit</th></tr>"));
+        assert!(html.contains(
+            "<tr><td>&lt;b&gt;Buffon&lt;/b&gt;</td><td style=\"text-align: right\">77</td></tr>"
+        ));
+        Ok(())
+    }
+}
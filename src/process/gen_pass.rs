@@ -1,55 +1,336 @@
-use anyhow::Result;
-use rand::prelude::SliceRandom;
+use crate::cli::gen_pass::WordCase;
+use anyhow::{bail, Context, Result};
+use rand::rngs::OsRng;
+use rand::Rng;
+use std::collections::{HashMap, HashSet};
+use std::fs;
 
 const UPPER: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ";
 const LOWER: &[u8] = b"abcdefghijkmnopqrstuvwxyz";
 const NUMBER: &[u8] = b"123456789";
 const SYMBOL: &[u8] = b"@#$%^&*_+=.";
+const DIGIT: &[u8] = b"0123456789";
+/// Longest password, guards against typos like `-l 10000000000`
+const MAX_LENGTH: usize = 4096;
 
+/// 7776 common English words in the diceware format, 12.9 bits per word
+const WORDLIST: &str = include_str!("../../assets/diceware_words.txt");
+
+pub struct PassphraseOptions {
+    pub words: usize,
+    pub separator: String,
+    pub case: WordCase,
+    /// Append a random digit to a random word
+    pub digit: bool,
+    /// Append a random symbol to a random word
+    pub symbol: bool,
+    /// Custom wordlist, one word per line, EFF-style lines like "11111\tabacus" also work
+    pub wordlist: Option<String>,
+}
+
+pub struct Passphrase {
+    pub phrase: String,
+    /// Entropy in bits, assuming the attacker knows the wordlist and the options
+    pub entropy: f64,
+}
+
+/// Random password with at least one character of every enabled class. Characters are drawn
+/// uniformly with replacement and passwords missing a class are rejected, so every valid
+/// password is equally likely.
 pub fn process_genpass(
-    length: u8,
+    length: usize,
     no_upper: bool,
     no_lower: bool,
     no_number: bool,
     no_symbol: bool,
 ) -> Result<String> {
-    let mut pass: Vec<u8> = Vec::with_capacity(length as usize);
-    let mut rng = rand::thread_rng();
-    let mut chars: Vec<u8> = Vec::new();
-    if !no_upper {
-        chars.extend(UPPER);
-        pass.push(*UPPER.choose(&mut rng).expect("UPPER won't be empty"));
+    let classes = char_classes(no_upper, no_lower, no_number, no_symbol)?;
+    if length > MAX_LENGTH {
+        bail!("Password length must be at most {}", MAX_LENGTH);
+    }
+    if length < classes.len() {
+        bail!(
+            "Password length must be at least {} to include every character class",
+            classes.len()
+        );
+    }
+
+    let pool: Vec<u8> = classes.concat();
+    let mut rng = OsRng;
+    let mut pass = vec![0u8; length];
+    loop {
+        for c in pass.iter_mut() {
+            *c = pool[rng.gen_range(0..pool.len())];
+        }
+        // 拒绝采样：缺少某类字符时整体重新生成，避免强制插入带来的偏差
+        if classes
+            .iter()
+            .all(|class| pass.iter().any(|c| class.contains(c)))
+        {
+            return Ok(String::from_utf8_lossy(&pass).to_string());
+        }
+    }
+}
+
+/// Entropy in bits of `process_genpass`, the log2 of the number of passwords it can produce
+pub fn genpass_entropy(
+    length: usize,
+    no_upper: bool,
+    no_lower: bool,
+    no_number: bool,
+    no_symbol: bool,
+) -> Result<f64> {
+    let classes = char_classes(no_upper, no_lower, no_number, no_symbol)?;
+    let total: usize = classes.iter().map(|class| class.len()).sum();
+    let n = total as f64;
+
+    // 容斥原理：包含每一类字符的密码数 = Σ (-1)^|S| (n - |S中字符|)^length，
+    // 以 n^length 为单位计算，长度很大时也不会溢出
+    let mut valid = 0.0;
+    for subset in 0..1u32 << classes.len() {
+        let excluded: usize = (0..classes.len())
+            .filter(|i| subset & (1 << i) != 0)
+            .map(|i| classes[i].len())
+            .sum();
+        let share = ((total - excluded) as f64 / n).powf(length as f64);
+        match subset.count_ones() % 2 {
+            0 => valid += share,
+            _ => valid -= share,
+        }
+    }
+    Ok(length as f64 * n.log2() + valid.log2())
+}
+
+fn char_classes(
+    no_upper: bool,
+    no_lower: bool,
+    no_number: bool,
+    no_symbol: bool,
+) -> Result<Vec<&'static [u8]>> {
+    let classes: Vec<&[u8]> = [
+        (no_upper, UPPER),
+        (no_lower, LOWER),
+        (no_number, NUMBER),
+        (no_symbol, SYMBOL),
+    ]
+    .into_iter()
+    .filter(|(disabled, _)| !disabled)
+    .map(|(_, class)| class)
+    .collect();
+    if classes.is_empty() {
+        bail!("At least one character class must be enabled");
+    }
+    Ok(classes)
+}
+
+/// Diceware-style passphrase, every choice is drawn uniformly from the OS random number generator
+pub fn process_passphrase(opts: &PassphraseOptions) -> Result<Passphrase> {
+    if opts.words == 0 {
+        bail!("A passphrase needs at least one word");
+    }
+    // 没有分隔符时单词边界有歧义，熵就不再精确
+    if opts.separator.is_empty() {
+        bail!("The separator must not be empty");
+    }
+    let content = match &opts.wordlist {
+        Some(path) => {
+            fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?
+        }
+        None => WORDLIST.to_string(),
+    };
+    // 先按大小写规则变换再去重，"Apple" 和 "apple" 只算一个单词
+    let words = cased_words(&parse_wordlist(&content), opts.case);
+    if words.len() < 2 {
+        bail!("The wordlist needs at least two distinct words");
     }
-    if !no_lower {
-        chars.extend(LOWER);
-        pass.push(*LOWER.choose(&mut rng).expect("LOWER won't be empty"));
+
+    let mut rng = OsRng;
+    let mut phrase: Vec<String> = (0..opts.words)
+        .map(|_| {
+            let word = &words[rng.gen_range(0..words.len())];
+            match opts.case {
+                WordCase::Random if rng.gen() => word.to_uppercase(),
+                _ => word.clone(),
+            }
+        })
+        .collect();
+
+    let n = opts.words as f64;
+    let mut entropy = n * word_entropy(&words, opts.case);
+    // 数字和符号分别追加到随机选中的单词末尾
+    for (enabled, chars) in [(opts.digit, DIGIT), (opts.symbol, SYMBOL)] {
+        if enabled {
+            let i = rng.gen_range(0..phrase.len());
+            phrase[i].push(chars[rng.gen_range(0..chars.len())] as char);
+            entropy += n.log2() + (chars.len() as f64).log2();
+        }
+    }
+
+    Ok(Passphrase {
+        phrase: phrase.join(&opts.separator),
+        entropy,
+    })
+}
+
+/// Distinct words of the list, the dice numbers of EFF wordlists are skipped
+fn parse_wordlist(content: &str) -> Vec<&str> {
+    let mut seen = HashSet::new();
+    content
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .filter_map(|line| line.split_whitespace().last())
+        .filter(|word| seen.insert(*word))
+        .collect()
+}
+
+/// Distinct words after applying the case, random case starts from the lowercase words
+fn cased_words(words: &[&str], case: WordCase) -> Vec<String> {
+    let mut seen = HashSet::new();
+    words
+        .iter()
+        .map(|word| match case {
+            WordCase::Lower | WordCase::Random => word.to_lowercase(),
+            WordCase::Upper => word.to_uppercase(),
+            WordCase::Title => title_case(word),
+        })
+        .filter(|word| seen.insert(word.clone()))
+        .collect()
+}
+
+/// Entropy in bits of one word drawn from the cased words
+fn word_entropy(words: &[String], case: WordCase) -> f64 {
+    let n = words.len() as f64;
+    if case != WordCase::Random {
+        return n.log2();
+    }
+    // 随机大小写时按实际输出的分布计算，没有字母的单词大小写相同，只有一种输出
+    let mut outputs: HashMap<String, f64> = HashMap::new();
+    for word in words {
+        let upper = word.to_uppercase();
+        if upper == *word {
+            *outputs.entry(upper).or_default() += 1.0 / n;
+        } else {
+            *outputs.entry(upper).or_default() += 0.5 / n;
+            *outputs.entry(word.clone()).or_default() += 0.5 / n;
+        }
+    }
+    outputs.values().map(|p| -p * p.log2()).sum()
+}
+
+fn title_case(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first
+            .to_uppercase()
+            .chain(chars.flat_map(char::to_lowercase))
+            .collect(),
+        None => String::new(),
     }
-    if !no_number {
-        chars.extend(NUMBER);
-        pass.push(*NUMBER.choose(&mut rng).expect("NUMBER won't be empty"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_genpass() -> Result<()> {
+        let pass = process_genpass(4, false, false, false, false)?;
+        assert_eq!(pass.len(), 4);
+        for class in [UPPER, LOWER, NUMBER, SYMBOL] {
+            assert!(pass.bytes().any(|c| class.contains(&c)));
+        }
+
+        // 远超字符池大小的长度，字符可以重复出现
+        let pass = process_genpass(MAX_LENGTH, false, true, true, true)?;
+        assert_eq!(pass.len(), MAX_LENGTH);
+        assert!(pass.bytes().all(|c| UPPER.contains(&c)));
+        assert!(process_genpass(MAX_LENGTH + 1, false, true, true, true).is_err());
+
+        assert!(process_genpass(3, false, false, false, false).is_err());
+        assert!(process_genpass(16, true, true, true, true).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_genpass_entropy() -> Result<()> {
+        // 只有一类字符时就是 length * log2(n)
+        let entropy = genpass_entropy(10, true, true, false, true)?;
+        assert!((entropy - 10.0 * 9f64.log2()).abs() < 1e-9);
+
+        // 两类字符、长度2：24 * 25 * 2 个有效密码
+        let entropy = genpass_entropy(2, false, false, true, true)?;
+        assert!((entropy - 1200f64.log2()).abs() < 1e-9);
+
+        let entropy = genpass_entropy(4000, false, false, false, false)?;
+        assert!((entropy - 4000.0 * 69f64.log2()).abs() < 1e-6);
+        Ok(())
     }
-    if !no_symbol {
-        chars.extend(SYMBOL);
-        pass.push(*SYMBOL.choose(&mut rng).expect("SYMBOL won't be empty"));
+
+    fn options(words: usize) -> PassphraseOptions {
+        PassphraseOptions {
+            words,
+            separator: "-".to_string(),
+            case: WordCase::Lower,
+            digit: false,
+            symbol: false,
+            wordlist: None,
+        }
     }
 
-    // 方案1
-    // for _ in 0..length {
-    //     let c = chars
-    //         .choose(&mut rng)
-    //         .expect("chars won't be empty in this context");
-    //     pass.push(*c as _); // 对 一个引用：&u8 解引用（拿其中的值）时，由于其为u8，所以获取值时会拷贝。
+    #[test]
+    fn test_passphrase_entropy() -> Result<()> {
+        let ret = process_passphrase(&options(6))?;
+        assert_eq!(ret.phrase.split('-').count(), 6);
+        let words = parse_wordlist(WORDLIST);
+        assert_eq!(words.len(), 7776);
+        assert!(ret.phrase.split('-').all(|w| words.contains(&w)));
+        assert!((ret.entropy - 6.0 * 7776f64.log2()).abs() < 1e-9);
+
+        let opts = PassphraseOptions {
+            case: WordCase::Title,
+            digit: true,
+            symbol: true,
+            separator: " ".to_string(),
+            ..options(4)
+        };
+        let ret = process_passphrase(&opts)?;
+        assert!(ret.phrase.starts_with(|c: char| c.is_ascii_uppercase()));
+        assert_eq!(ret.phrase.chars().filter(|c| c.is_ascii_digit()).count(), 1);
+        let expected = 4.0 * 7776f64.log2() + 2.0 * 4f64.log2() + 10f64.log2() + 11f64.log2();
+        assert!((ret.entropy - expected).abs() < 1e-9);
 
-    // 方案2
-    if length < 4 {
-        return Err(anyhow::anyhow!("Password length must be greater than 4"));
+        assert!(process_passphrase(&options(0)).is_err());
+        let opts = PassphraseOptions {
+            separator: String::new(),
+            ..options(6)
+        };
+        assert!(process_passphrase(&opts).is_err());
+        Ok(())
     }
 
-    chars.shuffle(&mut rng);
-    let pass_chars = chars.split_at(length as usize - pass.len());
-    pass.extend_from_slice(pass_chars.0);
-    pass.shuffle(&mut rng);
-    let password = String::from_utf8_lossy(&pass);
+    #[test]
+    fn test_case_collisions() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("rcli-words-{}.txt", std::process::id()));
+        std::fs::write(&path, "Apple\napple\nPEAR\npear\n")?;
+        let opts = PassphraseOptions {
+            wordlist: Some(path.to_string_lossy().to_string()),
+            ..options(3)
+        };
+        // 变换大小写后只剩两个不同的单词
+        let ret = process_passphrase(&opts)?;
+        assert_eq!(ret.entropy, 3.0);
+        std::fs::remove_file(path)?;
 
-    Ok(password.to_string())
+        let words = cased_words(&["Apple", "apple", "2024"], WordCase::Random);
+        assert_eq!(words, ["apple", "2024"]);
+        // apple 有两种输出，各 1/4；2024 只有一种输出，1/2
+        assert!((word_entropy(&words, WordCase::Random) - 1.5).abs() < 1e-9);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_wordlist() {
+        let words = parse_wordlist("11111\tabacus\n11112\tabdomen\n\n# comment\nabacus\nzebra\n");
+        assert_eq!(words, ["abacus", "abdomen", "zebra"]);
+    }
 }
@@ -50,6 +50,6 @@ pub fn process_verify(token: &str, aud: &str, secret_path: &str) -> Result<bool>
 }
 
 pub fn process_generate(len: u8) -> Result<String> {
-    let secret = process_genpass(len, false, false, false, false)?;
+    let secret = process_genpass(len.into(), false, false, false, false)?;
     Ok(secret)
 }
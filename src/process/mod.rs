@@ -1,14 +1,23 @@
 mod b64;
 mod crypto;
-mod csv_convert;
+mod csv;
 mod gen_pass;
 mod http_serve;
 mod jwt;
 mod text;
 
 pub(crate) use b64::{process_decode, process_encode};
-pub(crate) use csv_convert::process_csv;
-pub(crate) use gen_pass::process_genpass;
+pub(crate) use csv::{
+    process_csv, process_csv_generate, process_diff, process_from_sqlite, process_join,
+    process_json_to_csv, process_mask, process_query, process_show, process_split, process_stats,
+    process_to_sqlite, process_validate, terminal_width, ConvertOptions, CsvDialect, DiffOptions,
+    FilterOptions, FlattenOptions, GenerateOptions, JoinOptions, Mapping, MaskOptions,
+    QueryOptions, ShowOptions, SplitBy, SplitOptions, StatsOptions, ToSqliteOptions,
+    TransformOptions, ValidateOptions, WriterOptions,
+};
+pub(crate) use gen_pass::{
+    genpass_entropy, process_genpass, process_passphrase, PassphraseOptions,
+};
 pub(crate) use http_serve::process_http_serve;
 pub(crate) use jwt::{process_generate, process_sign, process_verify};
 pub(crate) use text::{process_text_generate, process_text_sign, process_text_verify};